@@ -0,0 +1,111 @@
+use config::Config;
+use runtime::Runtime;
+use nodes::template_node::{Template, TemplateNode};
+use nodes::view_node::View;
+use nodes::store_node::RootStore;
+
+
+/// All JS of a template in one output: runtime support, views and the root store.
+/// Source maps are generated for the bundle, since positions in separately rendered outputs
+/// no longer match once they are concatenated into a page.
+#[derive(Clone, Debug)]
+pub struct Bundle {
+    template_name: String,
+    runtime: Runtime,
+    views: Vec<View>,
+    root_store: RootStore,
+    config: Config
+}
+
+impl Bundle {
+    pub fn new(template: &Template, config: &Config) -> Bundle {
+        let views = template.nodes().iter().filter_map(|node| match node {
+            &TemplateNode::ViewNode(_, ref view) => Some(view.clone()),
+            _ => None
+        }).collect();
+
+        Bundle {
+            template_name: template.name().to_owned(),
+            runtime: Runtime::new(template.name(), config),
+            views: views,
+            root_store: template.root_store(config.store_runtime),
+            config: config.clone()
+        }
+    }
+
+    /// Name of the generated JS file the source map refers to, e.g. `main.js`
+    pub fn file_name(&self) -> String {
+        format!("{}.js", self.template_name)
+    }
+
+    /// The source map of the bundle, as a separate output
+    pub fn source_map(&self) -> BundleSourceMap {
+        BundleSourceMap { bundle: self.clone() }
+    }
+}
+
+/// Source map for the JS of a `Bundle`, rendered as JSON
+#[derive(Clone, Debug)]
+pub struct BundleSourceMap {
+    bundle: Bundle
+}
+
+mod output {
+    use super::{Bundle, BundleSourceMap};
+    use syntax::ext::base::ExtCtxt;
+    use js_write::{WriteJsFunctions, JsWriteFunctions};
+    use js_write::output_strings::js_functions_output;
+    use js_backend::JsBackend;
+    use source_map::{SourceMap, SourceMapMode, url_comment};
+    use codegen::lang::Js;
+    use codegen::named_output::{NamedOutput, NamedOutputType};
+    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
+
+    /// The functions of the bundle. Not implemented by `Bundle` itself, which writes the source map comment as well.
+    struct BundleFunctions<'b>(&'b Bundle);
+
+    impl<'b> WriteJsFunctions for BundleFunctions<'b> {
+        fn write_js_functions(&self, funcs: &mut JsWriteFunctions, backend: &JsBackend) {
+            let bundle = self.0;
+            bundle.runtime.write_js_functions(funcs, backend);
+            bundle.views.write_js_functions(funcs, backend);
+            bundle.root_store.write_js_functions(funcs, backend);
+        }
+    }
+
+    impl WriteOutputStrings<Js> for Bundle {
+        fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Js>) {
+            let (out, spans) = js_functions_output(&BundleFunctions(self), &self.config);
+            w.write_output_string(ecx, &out);
+
+            match self.config.source_map {
+                SourceMapMode::Off => {},
+                SourceMapMode::Inline => {
+                    let source_map = SourceMap::from_spans(ecx.codemap(), &self.file_name(), &out, &spans);
+                    w.write_output_string(ecx, &source_map.to_inline_comment());
+                },
+                SourceMapMode::File => {
+                    w.write_output_string(ecx, &url_comment(&format!("{}.map", self.file_name())));
+                }
+            };
+        }
+    }
+
+    impl WriteOutputStrings<Js> for BundleSourceMap {
+        fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Js>) {
+            let (out, spans) = js_functions_output(&BundleFunctions(&self.bundle), &self.bundle.config);
+            let source_map = SourceMap::from_spans(ecx.codemap(), &self.bundle.file_name(), &out, &spans);
+            w.write_output_string(ecx, &source_map.to_json());
+        }
+    }
+
+    impl NamedOutput<Js> for Bundle {
+        fn output_name(&self) -> &str { &self.template_name }
+        fn output_type(&self) -> NamedOutputType { NamedOutputType::BundleOutput }
+    }
+
+    impl NamedOutput<Js> for BundleSourceMap {
+        fn output_name(&self) -> &str { &self.bundle.template_name }
+        fn output_type(&self) -> NamedOutputType { NamedOutputType::SourceMapOutput }
+    }
+}
@@ -25,6 +25,7 @@ pub trait IntoBlock {
 pub mod lang {
     pub enum Html {}
     pub enum Js {}
+    pub enum Ts {}
 
     pub trait Lang {
         fn ext() -> &'static str;
@@ -39,6 +40,7 @@ pub mod lang {
     }
     lang!(Html, "html");
     lang!(Js, "js");
+    lang!(Ts, "d.ts");
 }
 
 pub mod ext {
@@ -69,6 +71,55 @@ pub mod ext {
     }
 }
 
+/// Names of the generated Rust items and JS functions, shared by the code generators and the plugin
+pub mod naming {
+    /// Rust render function, e.g. `rusttemplate_render_template_main_view_root_html`
+    pub fn render_fn_name(template_name: &str, output_type: &str, output_name: &str, lang: &str) -> String {
+        format!("rusttemplate_render_template_{}_{}_{}_{}", template_name, output_type, output_name, lang)
+    }
+
+    /// JS function rendering a view from the root store state, e.g. `rusttemplate_render_template_main_view_root_calls`
+    pub fn view_calls_fn_name(template_name: &str, view_name: &str) -> String {
+        format!("rusttemplate_render_template_{}_view_{}_calls", template_name, view_name)
+    }
+
+    /// JS reducer function for a store, e.g. `rusttemplate_store_template_main_counter`
+    pub fn store_fn_name(template_name: &str, store_name: &str) -> String {
+        format!("rusttemplate_store_template_{}_{}", template_name, store_name)
+    }
+
+    /// JS reducer combining all stores of a template, e.g. `rusttemplate_store_template_main`
+    pub fn root_store_fn_name(template_name: &str) -> String {
+        format!("rusttemplate_store_template_{}", template_name)
+    }
+
+    /// JS function creating the root store with the configured runtime, e.g. `rusttemplate_create_store_template_main`
+    pub fn create_store_fn_name(template_name: &str) -> String {
+        format!("rusttemplate_create_store_template_{}", template_name)
+    }
+
+    /// Action generated for `bind:value` on a store or store field, e.g. `SET_PERSON_FIRST_NAME` for `person.first_name`
+    pub fn bind_action_name(path: &str) -> String {
+        format!("SET_{}", bind_payload_member(path).to_uppercase())
+    }
+
+    /// Member of the generated action holding the new value, named after the whole bound path
+    /// so that fields of the same name in different stores get different actions, e.g. `person_first_name`
+    pub fn bind_payload_member(path: &str) -> String {
+        path.replace('.', "_")
+    }
+
+    /// Rust variable for a name bound in a view, such as by a match arm, e.g. `local_data`
+    pub fn local_var_name(name: &str) -> String {
+        format!("local_{}", name)
+    }
+
+    /// JS function rendering a view into a root element with the configured backend, e.g. `rusttemplate_patch_template_main`
+    pub fn patch_fn_name(template_name: &str) -> String {
+        format!("rusttemplate_patch_template_{}", template_name)
+    }
+}
+
 pub mod named_output {
     use super::lang::{Lang, Html, Js};
     use syntax::ext::base::ExtCtxt;
@@ -77,14 +128,26 @@ pub mod named_output {
 
     pub enum NamedOutputType {
         ViewOutput,
-        StoreOutput
+        StoreOutput,
+        ModelOutput,
+        TypesOutput,
+        RootStoreOutput,
+        RuntimeOutput,
+        BundleOutput,
+        SourceMapOutput
     }
 
     impl ToString for NamedOutputType {
         fn to_string(&self) -> String {
             match self {
                 &NamedOutputType::ViewOutput => "view".to_owned(),
-                &NamedOutputType::StoreOutput => "store".to_owned()
+                &NamedOutputType::StoreOutput => "store".to_owned(),
+                &NamedOutputType::ModelOutput => "model".to_owned(),
+                &NamedOutputType::TypesOutput => "types".to_owned(),
+                &NamedOutputType::RootStoreOutput => "root_store".to_owned(),
+                &NamedOutputType::RuntimeOutput => "runtime".to_owned(),
+                &NamedOutputType::BundleOutput => "bundle".to_owned(),
+                &NamedOutputType::SourceMapOutput => "source_map".to_owned()
             }
         }
     }
@@ -116,27 +179,125 @@ pub mod output_string_writer {
 
     pub trait OutputStringWrite<L: Lang> {
         fn write_output_string<'cx>(&mut self, ecx: &'cx ExtCtxt, contents: &str);
+
+        /// Write the value found at `path` in the dynamic state when rendering
+        fn write_dynamic_lookup<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str);
+
+        /// Write `contents` if the value found at `path` in the dynamic state is true when rendering
+        fn write_output_string_if<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str, contents: &str) {
+            self.write_output_string(ecx, contents);
+        }
+
+        /// Send the output written so far when rendering, if the writer streams
+        fn write_flush<'cx>(&mut self, ecx: &'cx ExtCtxt) {}
+
+        /// Write the arm matching the variant of the enum found at `path` when rendering
+        fn write_match<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str, arms: Vec<MatchArmStrings>) {}
+
+        /// Write the output strings with `name` bound to the value found at `path` when rendering
+        fn write_let<'cx>(&mut self, ecx: &'cx ExtCtxt, name: &str, path: &str, output_strings: Vec<OutputString>) {}
+
+        /// Write the result of an expression evaluated when rendering, such as `counter + 1`
+        fn write_expr<'cx>(&mut self, ecx: &'cx ExtCtxt, expr: OutputExpr) {}
     }
 
+    /// Output string which is either known statically, or looked up in the state when rendering
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum OutputString {
+        Static(String),
+        DynamicLookup(String),
+        /// Static string written if the value at the path is true, such as a toggled class
+        StaticIf(String, String),
+        Flush,
+        /// Output of the arm matching the variant of the enum at the path
+        Match(String, Vec<MatchArmStrings>),
+        /// Output with a local bound to the value at the path
+        Let(String, String, Vec<OutputString>),
+        /// Result of an expression, evaluated as in JS so the server renders what the client would
+        Expr(OutputExpr)
+    }
+
+    /// Expression written by an `OutputString::Expr`
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum OutputExpr {
+        Lookup(String),
+        String(String),
+        Number(i64),
+        Plus(Box<OutputExpr>, Box<OutputExpr>),
+        Minus(Box<OutputExpr>, Box<OutputExpr>)
+    }
+
+    /// Arm of an `OutputString::Match`, as in `output_actions::MatchArmActions`
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct MatchArmStrings {
+        pub variant: Option<String>,
+        pub bindings: Vec<String>,
+        pub output_strings: Vec<OutputString>
+    }
+
+    impl<L: Lang> OutputStringWrite<L> for Vec<OutputString> {
+        fn write_output_string<'cx>(&mut self, ecx: &'cx ExtCtxt, contents: &str) {
+            self.push(OutputString::Static(contents.to_owned()));
+        }
+
+        fn write_dynamic_lookup<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str) {
+            self.push(OutputString::DynamicLookup(path.to_owned()));
+        }
+
+        fn write_output_string_if<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str, contents: &str) {
+            self.push(OutputString::StaticIf(path.to_owned(), contents.to_owned()));
+        }
+
+        fn write_flush<'cx>(&mut self, ecx: &'cx ExtCtxt) {
+            self.push(OutputString::Flush);
+        }
+
+        fn write_match<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str, arms: Vec<MatchArmStrings>) {
+            self.push(OutputString::Match(path.to_owned(), arms));
+        }
+
+        fn write_let<'cx>(&mut self, ecx: &'cx ExtCtxt, name: &str, path: &str, output_strings: Vec<OutputString>) {
+            self.push(OutputString::Let(name.to_owned(), path.to_owned(), output_strings));
+        }
+
+        fn write_expr<'cx>(&mut self, ecx: &'cx ExtCtxt, expr: OutputExpr) {
+            self.push(OutputString::Expr(expr));
+        }
+    }
+
+    // Plain strings have no state to look up, so write the path itself as with other references
+
     impl<L: Lang> OutputStringWrite<L> for Vec<String> {
         fn write_output_string<'cx>(&mut self, ecx: &'cx ExtCtxt, contents: &str) {
             self.push(contents.to_owned());
         }
+
+        fn write_dynamic_lookup<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str) {
+            self.push(path.to_owned());
+        }
     }
 
     impl<L: Lang> OutputStringWrite<L> for String {
         fn write_output_string<'cx>(&mut self, ecx: &'cx ExtCtxt, contents: &str) {
             self.push_str(&contents);
         }
+
+        fn write_dynamic_lookup<'cx>(&mut self, ecx: &'cx ExtCtxt, path: &str) {
+            self.push_str(path);
+        }
     }
 }
 
 pub mod output_stmt_writer {
     use syntax::codemap::{Span, DUMMY_SP};
     use syntax::ext::base::ExtCtxt;
+    use syntax::ext::build::AstBuilder;
     use syntax::ast;
+    use syntax::ptr::P;
     use super::lang::{Lang, Html, Js};
-    use super::output_string_writer::WriteOutputStrings;
+    use super::naming::local_var_name;
+    use super::output_string_writer::{WriteOutputStrings, OutputString, OutputExpr, MatchArmStrings};
+    use model::ENUM_VALUES;
 
     pub trait WriteOutputStmts<L: Lang> {
         fn write_output_stmts<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStmtWrite<L>, writer: ast::Ident);
@@ -148,28 +309,177 @@ pub mod output_stmt_writer {
 
     impl<L: Lang, S: WriteOutputStrings<L>> WriteOutputStmts<L> for S {
         fn write_output_stmts<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStmtWrite<L>, writer: ast::Ident) {
-            let mut output_strings: Vec<String> = vec![];
+            let mut output_strings: Vec<OutputString> = vec![];
 
-            ecx.span_warn(DUMMY_SP, &format!("Writing output strings"));
+            debug!("Writing output strings");
             &self.write_output_strings(ecx, &mut output_strings);
 
             for output_string in &output_strings {
-                ecx.span_warn(DUMMY_SP, &format!("Writing output string: {}", &output_string));
+                debug!("Writing output string: {:?}", &output_string);
             }
 
-            let stmts = output_strings.iter()
-                .map(|s| {
-                    quote_stmt!(ecx, {
-                        write!($writer, "{}", &$s).unwrap();
-                    }).unwrap()
-                });
-
-            for stmt in stmts {
+            for stmt in output_string_stmts(ecx, &output_strings, writer, &[]) {
                 w.write_output_stmt(stmt);
             }
         }
     }
 
+    /// Lookup of `path` when rendering, as an `Option<&Value>`: in a local bound in the view
+    /// if the path starts with its name, otherwise in the state
+    fn lookup_expr<'cx>(ecx: &'cx ExtCtxt, path: &str, locals: &[String]) -> P<ast::Expr> {
+        let mut segments = path.splitn(2, '.');
+        let root = segments.next().unwrap_or("");
+        if !locals.iter().any(|local| local == root) {
+            return quote_expr!(ecx, state.lookup($path));
+        }
+
+        let local = ecx.ident_of(&local_var_name(root));
+        match segments.next() {
+            Some(rest) => quote_expr!(ecx, $local.and_then(|value| value.lookup($rest))),
+            None => quote_expr!(ecx, $local)
+        }
+    }
+
+    /// Owned `Value` of the expression when rendering, e.g. `state.lookup("counter").cloned().unwrap_or(Value::Null).plus(&Value::from(1i64))`
+    fn value_expr<'cx>(ecx: &'cx ExtCtxt, expr: &OutputExpr, locals: &[String]) -> P<ast::Expr> {
+        match expr {
+            &OutputExpr::Lookup(ref path) => {
+                let lookup = lookup_expr(ecx, path, locals);
+                quote_expr!(ecx, $lookup.cloned().unwrap_or(::incrust_common::value::Value::Null))
+            },
+            &OutputExpr::String(ref s) => {
+                let s = s.as_str();
+                quote_expr!(ecx, ::incrust_common::value::Value::from($s))
+            },
+            &OutputExpr::Number(n) => quote_expr!(ecx, ::incrust_common::value::Value::from($n)),
+            &OutputExpr::Plus(ref lhs, ref rhs) => {
+                let (lhs, rhs) = (value_expr(ecx, lhs, locals), value_expr(ecx, rhs, locals));
+                quote_expr!(ecx, $lhs.plus(&$rhs))
+            },
+            &OutputExpr::Minus(ref lhs, ref rhs) => {
+                let (lhs, rhs) = (value_expr(ecx, lhs, locals), value_expr(ecx, rhs, locals));
+                quote_expr!(ecx, $lhs.minus(&$rhs))
+            }
+        }
+    }
+
+    /// `match matched.and_then(|value| value.variant()) { Some("Loaded") => { let local_data = ...; ... }, _ => {} }`.
+    /// Arms after `_` or for a variant matched before are left out, as they can't be reached.
+    fn match_stmt<'cx>(ecx: &'cx ExtCtxt, path: &str, arms: &[MatchArmStrings], writer: ast::Ident, locals: &[String]) -> ast::Stmt {
+        let mut match_arms = Vec::new();
+        let mut matched_variants: Vec<&str> = Vec::new();
+        let mut has_wildcard = false;
+
+        for arm in arms {
+            if has_wildcard {
+                break;
+            }
+
+            let pat = match arm.variant {
+                Some(ref variant) if matched_variants.contains(&variant.as_str()) => { continue; },
+                Some(ref variant) => {
+                    let variant = variant.as_str();
+                    matched_variants.push(variant);
+                    quote_pat!(ecx, Some($variant))
+                },
+                None => {
+                    has_wildcard = true;
+                    ecx.pat_wild(DUMMY_SP)
+                }
+            };
+
+            let mut arm_locals = locals.to_vec();
+            let mut stmts = Vec::new();
+            for (idx, binding) in arm.bindings.iter().enumerate().filter(|&(_, binding)| binding != "_") {
+                let local = ecx.ident_of(&local_var_name(binding));
+                let value_path = format!("{}.{}", ENUM_VALUES, idx);
+                stmts.push(quote_stmt!(ecx, let $local = matched.and_then(|value| value.lookup($value_path))).unwrap());
+                arm_locals.push(binding.to_owned());
+            }
+            stmts.extend(output_string_stmts(ecx, &arm.output_strings, writer, &arm_locals));
+
+            match_arms.push(ecx.arm(DUMMY_SP, vec![pat], ecx.expr_block(ecx.block(DUMMY_SP, stmts))));
+        }
+
+        if !has_wildcard {
+            match_arms.push(ecx.arm(DUMMY_SP, vec![ecx.pat_wild(DUMMY_SP)], quote_expr!(ecx, {})));
+        }
+
+        let lookup = lookup_expr(ecx, path, locals);
+        let match_expr = ecx.expr_match(DUMMY_SP, quote_expr!(ecx, matched.and_then(|value| value.variant())), match_arms);
+        quote_stmt!(ecx, {
+            let matched = $lookup;
+            $match_expr;
+        }).unwrap()
+    }
+
+    /// `{ let local_city = state.lookup("data.profile.address.city"); ... }`
+    fn let_stmt<'cx>(ecx: &'cx ExtCtxt, name: &str, path: &str, output_strings: &[OutputString], writer: ast::Ident, locals: &[String]) -> ast::Stmt {
+        let local = ecx.ident_of(&local_var_name(name));
+        let lookup = lookup_expr(ecx, path, locals);
+
+        let mut let_locals = locals.to_vec();
+        let_locals.push(name.to_owned());
+
+        let mut stmts = vec![quote_stmt!(ecx, let $local = $lookup).unwrap()];
+        stmts.extend(output_string_stmts(ecx, output_strings, writer, &let_locals));
+        ecx.stmt_expr(ecx.expr_block(ecx.block(DUMMY_SP, stmts)))
+    }
+
+    /// Statements writing the output strings, with `locals` bound by the enclosing arms
+    fn output_string_stmts<'cx>(ecx: &'cx ExtCtxt, output_strings: &[OutputString], writer: ast::Ident, locals: &[String]) -> Vec<ast::Stmt> {
+        output_strings.iter()
+            .map(|output_string| {
+                match output_string {
+                    &OutputString::Static(ref s) => {
+                        // A string literal, so no formatting at runtime
+                        let s = s.as_str();
+                        quote_stmt!(ecx, {
+                            try!($writer.write_static($s));
+                        }).unwrap()
+                    },
+
+                    &OutputString::DynamicLookup(ref path) => {
+                        let lookup = lookup_expr(ecx, path, locals);
+                        quote_stmt!(ecx, {
+                            if let Some(value) = $lookup {
+                                try!(::incrust_common::render::write_html_text($writer, value));
+                            }
+                        }).unwrap()
+                    },
+
+                    &OutputString::StaticIf(ref path, ref s) => {
+                        let s = s.as_str();
+                        let lookup = lookup_expr(ecx, path, locals);
+                        quote_stmt!(ecx, {
+                            if $lookup.map_or(false, |value| value.is_truthy()) {
+                                try!($writer.write_static($s));
+                            }
+                        }).unwrap()
+                    },
+
+                    &OutputString::Flush => {
+                        quote_stmt!(ecx, {
+                            try!($writer.flush_chunk());
+                        }).unwrap()
+                    },
+
+                    &OutputString::Match(ref path, ref arms) => match_stmt(ecx, path, arms, writer, locals),
+
+                    &OutputString::Let(ref name, ref path, ref output_strings) => let_stmt(ecx, name, path, output_strings, writer, locals),
+
+                    &OutputString::Expr(ref expr) => {
+                        let value = value_expr(ecx, expr, locals);
+                        quote_stmt!(ecx, {
+                            let value = $value;
+                            try!(::incrust_common::render::write_html_text($writer, &value));
+                        }).unwrap()
+                    }
+                }
+            })
+            .collect()
+    }
+
     impl<'s, L: Lang> OutputStmtWrite<L> for Vec<ast::Stmt> {
         fn write_output_stmt(&mut self, stmt: ast::Stmt) {
             self.push(stmt);
@@ -214,6 +524,7 @@ pub mod output_item_writer {
     use codegen::IntoBlock;
     use codegen::lang::{Lang, Html, Js};
     use codegen::named_output::NamedOutput;
+    use codegen::naming::render_fn_name;
     use nodes::view_node::View;
     use nodes::template_node::Template;
 
@@ -227,22 +538,24 @@ pub mod output_item_writer {
     }
 
     pub trait IntoOutputItem<L: Lang> {
-        fn into_output_item<'cx>(&self, ecx: &'cx ExtCtxt, name: &str) -> P<ast::Item>;
+        /// Write the render function for the output, as part of the given template
+        fn into_output_item<'cx>(&self, ecx: &'cx ExtCtxt, template_name: &str) -> P<ast::Item>;
     }
 
     macro_rules! lang_impl (
         ($lang: ty) => (
             impl<S: WriteOutputStmts<$lang> + NamedOutput<$lang>> IntoOutputItem<$lang> for S {
-                fn into_output_item<'cx>(&self, ecx: &'cx ExtCtxt, name: &str) -> P<ast::Item> {
+                fn into_output_item<'cx>(&self, ecx: &'cx ExtCtxt, template_name: &str) -> P<ast::Item> {
                     let lang = stringify!($lang).to_lowercase();
                     let output_name = self.output_name();
                     let output_type = self.output_type().to_string().to_lowercase();
 
-                    let item_name = ecx.ident_of(&format!("rusttemplate_render_template_{}_{}_{}_{}", "main", &output_type, &output_name, &lang));
-                    ecx.span_warn(DUMMY_SP, &format!("Writing item {}", item_name.to_string()));
+                    let item_name = ecx.ident_of(&render_fn_name(template_name, &output_type, &output_name, &lang));
+                    debug!("Writing item {}", item_name.to_string());
 
                     let html_writer = ecx.ident_of("html_writer");
                     let js_writer = ecx.ident_of("js_writer");
+                    let state = ecx.ident_of("state");
 
                     let block = {
                         let mut out = Vec::new();
@@ -254,23 +567,24 @@ pub mod output_item_writer {
                             _ if lang == "js" => {
                                 self.write_output_stmts(ecx, &mut out, js_writer);
                             },
+                            // Declarations are written alongside the generated JS
+                            _ if lang == "ts" => {
+                                self.write_output_stmts(ecx, &mut out, js_writer);
+                            },
                             _ => {
-                                ecx.span_warn(DUMMY_SP, &format!("Unsupported language, won't render: {:?}", stringify!($lang)));
+                                debug!("Unsupported language, won't render: {:?}", stringify!($lang));
                             }
                         }
+                        out.push(ecx.stmt_expr(quote_expr!(ecx, Ok(()))));
                         ecx.block(DUMMY_SP, out)
                     };
 
-                    let item = {
-                        let args = vec![
-                            ecx.arg(DUMMY_SP, html_writer, quote_ty!(ecx, &mut String)),
-                            ecx.arg(DUMMY_SP, js_writer, quote_ty!(ecx, &mut String)),
-                        ];
-                        let ret_ty = quote_ty!(ecx, ());
-                        ecx.item_fn(DUMMY_SP, item_name, args, ret_ty, block)
-                    };
-
-                    item
+                    // Generic over the writers, so output can be streamed instead of built in memory
+                    quote_item!(ecx,
+                        fn $item_name<H: ::incrust_common::render::RenderWrite, J: ::incrust_common::render::RenderWrite>(
+                            $html_writer: &mut H, $js_writer: &mut J, $state: &::incrust_common::value::Value
+                        ) -> ::incrust_common::render::RenderResult $block
+                    ).unwrap()
                 }
             }
 
@@ -361,10 +675,11 @@ pub mod output_item_writer {
     );
     lang_impl!(Html);
     lang_impl!(Js);
+    lang_impl!(Ts);
 
     impl<'s, L: Lang> OutputItemWrite<L> for Vec<P<ast::Item>> {
         fn write_output_item<'cx>(&mut self, ecx: &'cx ExtCtxt, item: P<ast::Item>) {
-            ecx.span_warn(DUMMY_SP, &format!("Writing output item: {:?}", &item));
+            debug!("Writing output item: {:?}", &item);
             self.push(item);
         }
     }
@@ -0,0 +1,56 @@
+use runtime::StoreRuntime;
+use js_backend::JsBackendKind;
+use source_map::SourceMapMode;
+use js_write::JsFormat;
+
+
+/// How the generated JS functions are made available
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsModuleFormat {
+    /// Global function declarations, for inline scripts (the default)
+    Global,
+    /// ES module exports, importing the runtime
+    Es
+}
+
+impl JsModuleFormat {
+    pub fn from_name(name: &str) -> Option<JsModuleFormat> {
+        match name {
+            "global" => Some(JsModuleFormat::Global),
+            "es" => Some(JsModuleFormat::Es),
+            _ => None
+        }
+    }
+}
+
+impl Default for JsModuleFormat {
+    fn default() -> JsModuleFormat { JsModuleFormat::Global }
+}
+
+/// Module specifier the built-in runtime is imported from in ES module output
+pub const DEFAULT_RUNTIME_MODULE: &'static str = "./incrust_runtime.js";
+
+/// Code generation options, from the plugin arguments:
+/// `#![plugin(incrust_plugin(store_runtime = "builtin", js_backend = "dom", js_module = "es"))]`
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub store_runtime: StoreRuntime,
+    pub js_backend: JsBackendKind,
+    pub js_module: JsModuleFormat,
+    pub runtime_module: String,
+    pub source_map: SourceMapMode,
+    pub js_format: JsFormat
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            store_runtime: StoreRuntime::default(),
+            js_backend: JsBackendKind::default(),
+            js_module: JsModuleFormat::default(),
+            runtime_module: DEFAULT_RUNTIME_MODULE.to_owned(),
+            source_map: SourceMapMode::default(),
+            js_format: JsFormat::default()
+        }
+    }
+}
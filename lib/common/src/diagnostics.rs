@@ -0,0 +1,247 @@
+use syntax::codemap::{Span, DUMMY_SP};
+use syntax::errors::{DiagnosticBuilder, Handler, Level};
+use syntax::parse::parser::Parser;
+use syntax::print::pprust::token_to_string;
+
+
+/// Declare the diagnostic codes from one table, so each variant and its code can't get out of step
+macro_rules! diagnostic_codes {
+    ($($variant:ident => $code:expr),*) => {
+        /// Error codes for template diagnostics.
+        /// Parser errors are numbered from IC0001, semantic (type checking) errors from IC0101.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum DiagnosticCode {
+            $($variant),*
+        }
+
+        /// Every code with its variant, in the order declared
+        const DIAGNOSTIC_CODES: &'static [(DiagnosticCode, &'static str)] = &[
+            $((DiagnosticCode::$variant, $code)),*
+        ];
+
+        impl DiagnosticCode {
+            pub fn code(&self) -> &'static str {
+                match *self {
+                    $(DiagnosticCode::$variant => $code),*
+                }
+            }
+        }
+    }
+}
+
+diagnostic_codes! {
+    UnexpectedToken => "IC0001",
+    UnknownTemplateKeyword => "IC0002",
+    UnknownStoreLabel => "IC0003",
+    UnsupportedLiteral => "IC0004",
+    VoidElementContents => "IC0005",
+    InvalidAttribute => "IC0006",
+    InvalidMatch => "IC0007",
+    InvalidLet => "IC0008",
+
+    UnresolvedName => "IC0101",
+    UnknownField => "IC0102",
+    UnknownActionPayload => "IC0103",
+    UnknownType => "IC0104",
+    NotAModel => "IC0105",
+    OperatorMismatch => "IC0106",
+    ReducerMismatch => "IC0107",
+    DuplicateModel => "IC0108",
+    MalformedExpression => "IC0109",
+    DuplicateView => "IC0110",
+    BindMismatch => "IC0111",
+    NotAnEnum => "IC0112",
+    UnknownVariant => "IC0113",
+    PatternMismatch => "IC0114",
+    NonExhaustiveMatch => "IC0115",
+    BindActionConflict => "IC0116"
+}
+
+impl DiagnosticCode {
+    /// Parse errors from the Rust parser itself have no template error code
+    fn from_code(code: &str) -> Option<DiagnosticCode> {
+        DIAGNOSTIC_CODES.iter()
+            .find(|&&(_, candidate)| candidate == code)
+            .map(|&(variant, _)| variant)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticLevel {
+    Error,
+    Warning
+}
+
+#[derive(Clone, Debug)]
+pub enum DiagnosticNote {
+    Note(Option<Span>, String),
+    Help(String),
+    Suggestion(Span, String, String)
+}
+
+/// A diagnostic about the template source, emitted as a regular compiler error or warning
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    level: DiagnosticLevel,
+    code: DiagnosticCode,
+    span: Span,
+    message: String,
+    notes: Vec<DiagnosticNote>
+}
+
+impl Diagnostic {
+    pub fn error(code: DiagnosticCode, span: Span, message: &str) -> Diagnostic {
+        Diagnostic {
+            level: DiagnosticLevel::Error,
+            code: code,
+            span: span,
+            message: message.to_owned(),
+            notes: vec![]
+        }
+    }
+
+    pub fn warning(code: DiagnosticCode, span: Span, message: &str) -> Diagnostic {
+        Diagnostic { level: DiagnosticLevel::Warning, ..Diagnostic::error(code, span, message) }
+    }
+
+    pub fn level(&self) -> DiagnosticLevel { self.level }
+    pub fn code(&self) -> DiagnosticCode { self.code }
+    pub fn span(&self) -> Span { self.span }
+    pub fn message(&self) -> &str { &self.message }
+    pub fn notes(&self) -> &[DiagnosticNote] { &self.notes }
+
+    pub fn note(mut self, message: &str) -> Diagnostic {
+        self.notes.push(DiagnosticNote::Note(None, message.to_owned()));
+        self
+    }
+
+    pub fn span_note(mut self, span: Span, message: &str) -> Diagnostic {
+        self.notes.push(DiagnosticNote::Note(Some(span), message.to_owned()));
+        self
+    }
+
+    pub fn help(mut self, message: &str) -> Diagnostic {
+        self.notes.push(DiagnosticNote::Help(message.to_owned()));
+        self
+    }
+
+    /// Suggest replacing the source at `span` with `replacement`
+    pub fn suggestion(mut self, span: Span, message: &str, replacement: &str) -> Diagnostic {
+        self.notes.push(DiagnosticNote::Suggestion(span, message.to_owned(), replacement.to_owned()));
+        self
+    }
+
+    /// Take over an error from the parser, so it can be collected instead of emitted right away
+    pub fn from_builder(mut db: DiagnosticBuilder) -> Diagnostic {
+        let code = db.code.as_ref()
+            .and_then(|code| DiagnosticCode::from_code(code))
+            .unwrap_or(DiagnosticCode::UnexpectedToken);
+        let span = db.span.primary_span().unwrap_or(DUMMY_SP);
+        let mut diagnostic = Diagnostic::error(code, span, &db.message);
+
+        for child in &db.children {
+            let note = match child.level {
+                Level::Help => DiagnosticNote::Help(child.message.to_owned()),
+                _ => DiagnosticNote::Note(child.span.primary_span(), child.message.to_owned())
+            };
+            diagnostic.notes.push(note);
+        }
+
+        db.cancel();
+        diagnostic
+    }
+
+    pub fn into_builder<'a>(self, handler: &'a Handler) -> DiagnosticBuilder<'a> {
+        let mut db = match self.level {
+            DiagnosticLevel::Error => handler.struct_span_err_with_code(self.span, &self.message, self.code.code()),
+            DiagnosticLevel::Warning => handler.struct_span_warn_with_code(self.span, &self.message, self.code.code())
+        };
+
+        for note in self.notes {
+            match note {
+                DiagnosticNote::Note(Some(span), ref message) => { db.span_note(span, message); },
+                DiagnosticNote::Note(None, ref message) => { db.note(message); },
+                DiagnosticNote::Help(ref message) => { db.help(message); },
+                DiagnosticNote::Suggestion(span, ref message, ref replacement) => {
+                    db.span_suggestion(span, message, replacement.to_owned());
+                }
+            };
+        }
+
+        db
+    }
+
+    pub fn emit(self, handler: &Handler) {
+        self.into_builder(handler).emit();
+    }
+}
+
+pub trait DiagnosticWrite {
+    fn write_diagnostic(&mut self, diagnostic: Diagnostic);
+
+    /// Record a parser error we are recovering from
+    fn write_parse_error(&mut self, db: DiagnosticBuilder) {
+        self.write_diagnostic(Diagnostic::from_builder(db));
+    }
+}
+
+impl DiagnosticWrite for Vec<Diagnostic> {
+    fn write_diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.push(diagnostic);
+    }
+}
+
+/// Returns true if any of the diagnostics is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.level() == DiagnosticLevel::Error)
+}
+
+/// Parser error for an unexpected token at the current position
+pub fn unexpected_token<'a>(parser: &Parser<'a>, context: &str, expected: &str) -> DiagnosticBuilder<'a> {
+    Diagnostic::error(DiagnosticCode::UnexpectedToken, parser.span, &format!("unexpected token in {}: `{}`", context, token_to_string(&parser.token)))
+        .note(&format!("expected {}", expected))
+        .into_builder(parser.diagnostic())
+}
+
+fn lev_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..b_chars.len() + 1).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b_chars.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let value = *[prev[j] + cost, prev[j + 1] + 1, current[j] + 1].iter().min().unwrap();
+            current.push(value);
+        }
+        prev = current;
+    }
+
+    prev[b_chars.len()]
+}
+
+/// Closest candidate to a misspelled name, if any is close enough to suggest
+pub fn closest_name<'n, I: Iterator<Item=&'n str>>(name: &str, candidates: I) -> Option<&'n str> {
+    let max_distance = ::std::cmp::max(name.len(), 3) / 3;
+    candidates
+        .map(|candidate| (lev_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[test]
+fn test_closest_name() {
+    let names = vec!["counter", "person"];
+    assert_eq!(closest_name("countr", names.iter().map(|s| *s)), Some("counter"));
+    assert_eq!(closest_name("xyz", names.iter().map(|s| *s)), None);
+}
+
+#[test]
+fn test_diagnostic_codes() {
+    for &(variant, code) in DIAGNOSTIC_CODES {
+        assert_eq!(variant.code(), code);
+        assert_eq!(DiagnosticCode::from_code(code), Some(variant));
+    }
+    assert_eq!(DiagnosticCode::from_code("E0308"), None);
+}
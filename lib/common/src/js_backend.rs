@@ -0,0 +1,402 @@
+use js_write::{JsWrite, JsWriteSimpleExpr, JsWriteParamList, WriteJsSimpleExpr};
+use nodes::element_node::{ElementAttribute, element_key};
+use nodes::element_node::output::write_js_attributes;
+
+
+/// Client side rendering library targeted by the generated view functions.
+/// Every backend is driven by the same `OutputAction` stream.
+pub trait JsBackend {
+    fn text(&self, js: &mut JsWrite, f: &Fn(&mut JsWriteSimpleExpr));
+    fn element_open(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute]);
+    fn element_close(&self, js: &mut JsWrite, element_type: &str);
+    fn element_void(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute]);
+
+    /// Element whose attributes and contents never change, identified by `key` within the view.
+    /// Backends which patch the page may render the contents only the first time.
+    fn static_element(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute], key: &str, contents: &Fn(&mut JsWrite)) {
+        self.element_open(js, element_type, attributes);
+        contents(js);
+        self.element_close(js, element_type);
+    }
+
+    /// JS object providing the backend
+    fn runtime_object(&self) -> &'static str;
+
+    /// JS function rendering a view into a root element: `(root, view, state)`
+    fn patch_fn(&self) -> &'static str;
+
+    /// JS source for the backend, to emit with the generated code
+    fn js_source(&self) -> Option<&'static str> { None }
+
+    /// JS configuring the backend once it is loaded, such as setting bound values as properties
+    fn js_setup(&self) -> Option<&'static str> { None }
+
+    /// ES module import for the backend, as binding and module specifier
+    fn es_import(&self, runtime_module: &str) -> (String, String);
+}
+
+/// Backend selected by the `js_backend` plugin option
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsBackendKind {
+    IncrementalDom,
+    Dom,
+    Vdom,
+    String
+}
+
+impl JsBackendKind {
+    pub fn from_name(name: &str) -> Option<JsBackendKind> {
+        match name {
+            "incremental_dom" => Some(JsBackendKind::IncrementalDom),
+            "dom" => Some(JsBackendKind::Dom),
+            "vdom" => Some(JsBackendKind::Vdom),
+            "string" => Some(JsBackendKind::String),
+            _ => None
+        }
+    }
+
+    pub fn all() -> Vec<JsBackendKind> {
+        vec![JsBackendKind::IncrementalDom, JsBackendKind::Dom, JsBackendKind::Vdom, JsBackendKind::String]
+    }
+
+    pub fn backend(&self) -> &'static JsBackend {
+        match *self {
+            JsBackendKind::IncrementalDom => &INCREMENTAL_DOM,
+            JsBackendKind::Dom => &DOM,
+            JsBackendKind::Vdom => &VDOM,
+            JsBackendKind::String => &STRING
+        }
+    }
+}
+
+impl Default for JsBackendKind {
+    fn default() -> JsBackendKind { JsBackendKind::IncrementalDom }
+}
+
+/// Backend calling a runtime object with the IncrementalDOM style
+/// `elementOpen`, `elementClose`, `elementVoid` and `text` functions
+pub struct RuntimeObjectBackend {
+    object: &'static str,
+    patch_fn: &'static str,
+    source: Option<&'static str>,
+    /// Package providing the runtime object, otherwise it is part of the built-in runtime module
+    package: Option<&'static str>,
+    /// Statement configuring the runtime object, see `JsBackend::js_setup`
+    setup: Option<&'static str>,
+    /// Whether the runtime keeps elements between patches, so static contents can be skipped with `skip()`
+    skip_static: bool
+}
+
+fn call_element(js: &mut JsWrite, method: &str, element_type: &str) {
+    js.call_method(method, &|pl| {
+        pl.param(&|ex| {
+            ex.string_lit(element_type);
+        });
+    });
+}
+
+/// Arguments as with IncrementalDOM's `elementOpen`: the element type, the key and statics
+/// (when needed), then pairs of attribute names and values. A `key=` given in the view is used
+/// over the generated `key`. With `statics`, the attributes go in the statics array instead,
+/// which IncrementalDOM only applies when it creates the element, so they must never change.
+fn element_params(pl: &mut JsWriteParamList, element_type: &str, key: Option<&str>, attributes: &[ElementAttribute], statics: bool) {
+    pl.param(&|ex| { ex.string_lit(element_type); });
+    if key.is_none() && attributes.is_empty() {
+        return;
+    }
+
+    pl.param(&|ex| {
+        match (element_key(attributes), key) {
+            (Some(simple_expr), _) => { simple_expr.write_js_simple_expr(ex); },
+            (None, Some(key)) => { ex.string_lit(key); },
+            (None, None) => { ex.var_reference("null"); }
+        };
+    });
+    let has_attributes = attributes.iter().any(|attribute| match attribute { &ElementAttribute::Key(_) => false, _ => true });
+    if statics && has_attributes {
+        pl.param(&|ex| { ex.array(&|pl| { write_js_attributes(attributes, pl); }); });
+    } else {
+        pl.param(&|ex| { ex.var_reference("null"); });
+        write_js_attributes(attributes, pl);
+    }
+}
+
+impl JsBackend for RuntimeObjectBackend {
+    fn text(&self, js: &mut JsWrite, f: &Fn(&mut JsWriteSimpleExpr)) {
+        js.call_method(&format!("{}.text", self.object), &|pl| {
+            pl.param(f);
+        });
+    }
+
+    fn element_open(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute]) {
+        js.call_method(&format!("{}.elementOpen", self.object), &|pl| {
+            element_params(pl, element_type, None, attributes, false);
+        });
+    }
+
+    fn element_close(&self, js: &mut JsWrite, element_type: &str) {
+        call_element(js, &format!("{}.elementClose", self.object), element_type);
+    }
+
+    fn element_void(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute]) {
+        js.call_method(&format!("{}.elementVoid", self.object), &|pl| {
+            element_params(pl, element_type, None, attributes, false);
+        });
+    }
+
+    /// Open the element with a key and its attributes as statics, so the same element is patched
+    /// each time, and flag it once its contents are rendered:
+    /// `let el = elementOpen("div", "key", ["id", "intro"]); if (el.__incrust_static) { skip(); } else { ... }`
+    fn static_element(&self, js: &mut JsWrite, element_type: &str, attributes: &[ElementAttribute], key: &str, contents: &Fn(&mut JsWrite)) {
+        if !self.skip_static {
+            self.element_open(js, element_type, attributes);
+            contents(js);
+            self.element_close(js, element_type);
+            return;
+        }
+
+        let element_open = format!("{}.elementOpen", self.object);
+        js.let_statement("static_element", &|js_simple| {
+            js_simple.call_params(&element_open, &|pl| {
+                element_params(pl, element_type, Some(key), attributes, true);
+            });
+        });
+        js.if_else(&|js_simple| { js_simple.var_reference("static_element.__incrust_static"); }, &|js| {
+            js.call_method(&format!("{}.skip", self.object), &|_| {});
+        }, &|js| {
+            contents(js);
+            js.assign("static_element.__incrust_static", &|js_simple| { js_simple.var_reference("true"); });
+        });
+        self.element_close(js, element_type);
+    }
+
+    fn runtime_object(&self) -> &'static str { self.object }
+
+    fn patch_fn(&self) -> &'static str { self.patch_fn }
+
+    fn js_source(&self) -> Option<&'static str> { self.source }
+
+    fn js_setup(&self) -> Option<&'static str> { self.setup }
+
+    fn es_import(&self, runtime_module: &str) -> (String, String) {
+        match self.package {
+            Some(package) => (format!("* as {}", self.object), package.to_owned()),
+            None => (format!("{{ {} }}", self.object), runtime_module.to_owned())
+        }
+    }
+}
+
+/// IncrementalDOM, loaded separately by the page (the default)
+pub static INCREMENTAL_DOM: RuntimeObjectBackend = RuntimeObjectBackend {
+    object: "IncrementalDOM",
+    patch_fn: "IncrementalDOM.patch",
+    source: None,
+    package: Some("incremental-dom"),
+    // The attributes of `element_node::DOM_PROPERTIES` only set the initial state of a form control,
+    // the properties are what it shows
+    setup: Some("IncrementalDOM.attributes.value = IncrementalDOM.attributes.checked = IncrementalDOM.attributes.selected = IncrementalDOM.applyProp;"),
+    skip_static: true
+};
+
+/// Plain DOM creation, replacing the contents of the root element on each render
+pub static DOM: RuntimeObjectBackend = RuntimeObjectBackend {
+    object: "IncrustDom",
+    patch_fn: "IncrustDom.patch",
+    source: Some(DOM_JS),
+    package: None,
+    setup: None,
+    skip_static: false
+};
+
+/// Virtual DOM nodes (`{ tag, attrs, children }`), handed to `IncrustVdom.apply` to update the page.
+/// Replace `IncrustVdom.apply` to diff the tree with a virtual DOM library.
+pub static VDOM: RuntimeObjectBackend = RuntimeObjectBackend {
+    object: "IncrustVdom",
+    patch_fn: "IncrustVdom.patch",
+    source: Some(VDOM_JS),
+    package: None,
+    setup: None,
+    skip_static: false
+};
+
+/// HTML strings, which can be built without a DOM such as in web workers (`IncrustString.render(view, state)`)
+pub static STRING: RuntimeObjectBackend = RuntimeObjectBackend {
+    object: "IncrustString",
+    patch_fn: "IncrustString.patch",
+    source: Some(STRING_JS),
+    package: None,
+    setup: None,
+    skip_static: false
+};
+
+const DOM_JS: &'static str = "var IncrustDom = IncrustDom || (function() {\
+var stack = [];\
+function top() { return stack[stack.length - 1]; }\
+function elementOpen(tag) {\
+var el = document.createElement(tag);\
+for (var i = 3; i < arguments.length; i += 2) { setAttribute(el, arguments[i], arguments[i + 1]); }\
+top().appendChild(el); stack.push(el); return el;\
+}\
+function setAttribute(el, name, value) {\
+if (name === 'style' && typeof value === 'object') { for (var prop in value) { el.style.setProperty(prop, value[prop]); } }\
+else if (name === 'value' || name === 'checked' || name === 'selected' || typeof value === 'function') { el[name] = value; }\
+else if (value != null) { el.setAttribute(name, value); }\
+}\
+return {\
+elementOpen: elementOpen,\
+elementClose: function(tag) { return stack.pop(); },\
+elementVoid: function(tag) { var el = elementOpen.apply(null, arguments); stack.pop(); return el; },\
+text: function(value) { top().appendChild(document.createTextNode(value)); },\
+patch: function(root, view, state) {\
+var fragment = document.createDocumentFragment(); stack = [fragment]; view(state); stack = [];\
+while (root.firstChild) { root.removeChild(root.firstChild); }\
+root.appendChild(fragment);\
+}\
+};\
+})();";
+
+const VDOM_JS: &'static str = "var IncrustVdom = IncrustVdom || (function() {\
+var stack = [];\
+function top() { return stack[stack.length - 1]; }\
+function elementOpen(tag) {\
+var node = { tag: tag, attrs: {}, children: [] };\
+for (var i = 3; i < arguments.length; i += 2) { node.attrs[arguments[i]] = arguments[i + 1]; }\
+top().children.push(node); stack.push(node); return node;\
+}\
+function toDom(node) {\
+if (typeof node === 'string') { return document.createTextNode(node); }\
+var el = document.createElement(node.tag);\
+for (var name in node.attrs) {\
+var value = node.attrs[name];\
+if (name === 'style' && typeof value === 'object') { for (var prop in value) { el.style.setProperty(prop, value[prop]); } }\
+else if (name === 'value' || name === 'checked' || name === 'selected' || typeof value === 'function') { el[name] = value; }\
+else if (value != null) { el.setAttribute(name, value); }\
+}\
+node.children.forEach(function(child) { el.appendChild(toDom(child)); });\
+return el;\
+}\
+var vdom = {\
+elementOpen: elementOpen,\
+elementClose: function(tag) { return stack.pop(); },\
+elementVoid: function(tag) { var node = elementOpen.apply(null, arguments); stack.pop(); return node; },\
+text: function(value) { top().children.push(String(value)); },\
+render: function(view, state) { var root = { tag: null, children: [] }; stack = [root]; view(state); stack = []; return root.children; },\
+apply: function(root, children) {\
+while (root.firstChild) { root.removeChild(root.firstChild); }\
+children.forEach(function(child) { root.appendChild(toDom(child)); });\
+},\
+patch: function(root, view, state) { vdom.apply(root, vdom.render(view, state)); }\
+};\
+return vdom;\
+})();";
+
+const STRING_JS: &'static str = "var IncrustString = IncrustString || (function() {\
+var out = [];\
+function escape(value) { return String(value).replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/\"/g, '&quot;'); }\
+function openTag(args) {\
+var tag = '<' + args[0];\
+for (var i = 3; i < args.length; i += 2) {\
+var value = args[i + 1];\
+if (args[i] === 'style' && typeof value === 'object') { value = Object.keys(value).map(function(prop) { return prop + ': ' + value[prop] + ';'; }).join(' '); }\
+if (value === true) { tag += ' ' + args[i]; }\
+else if (value != null && value !== false && typeof value !== 'function') { tag += ' ' + args[i] + '=\"' + escape(value) + '\"'; }\
+}\
+return tag;\
+}\
+var html = {\
+elementOpen: function(tag) { out.push(openTag(arguments) + '>'); },\
+elementClose: function(tag) { out.push('</' + tag + '>'); },\
+elementVoid: function(tag) { out.push(openTag(arguments) + ' />'); },\
+text: function(value) { out.push(escape(value)); },\
+render: function(view, state) { out = []; view(state); var result = out.join(''); out = []; return result; },\
+patch: function(root, view, state) { root.innerHTML = html.render(view, state); }\
+};\
+return html;\
+})();";
+
+#[test]
+fn test_backends() {
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions};
+
+    fn write_view(funcs: &mut JsWriteFunctions, backend: &JsBackend) {
+        funcs.function("view", vec!["state"], &|js| {
+            backend.element_open(js, "p", &[]);
+            backend.text(js, &|ex| { ex.var_reference("state.counter"); });
+            backend.element_close(js, "p");
+        });
+    }
+
+    let backends = [
+        (JsBackendKind::IncrementalDom, "IncrementalDOM"),
+        (JsBackendKind::Dom, "IncrustDom"),
+        (JsBackendKind::Vdom, "IncrustVdom"),
+        (JsBackendKind::String, "IncrustString")
+    ];
+    for &(kind, object) in &backends {
+        let backend = kind.backend();
+        let mut out = String::new();
+        write_view(&mut JsFormatter::new(&mut out, JsFormat::Minified), backend);
+        assert_eq!(out, format!("function view(a){{{0}.elementOpen(\"p\");{0}.text(a.counter);{0}.elementClose(\"p\");}}", object));
+
+        // Only IncrementalDOM is loaded separately
+        assert_eq!(backend.js_source().is_some(), kind != JsBackendKind::IncrementalDom);
+        assert_eq!(backend.runtime_object(), object);
+    }
+}
+
+#[test]
+fn test_static_element() {
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions};
+    use testing::{with_ecx, parse_template_source, first_element};
+
+    fn write_view(funcs: &mut JsWriteFunctions, backend: &JsBackend, attributes: &[ElementAttribute]) {
+        funcs.function("view", vec!["state"], &|js| {
+            backend.static_element(js, "div", attributes, "incrust-static-0", &|js| {
+                backend.text(js, &|ex| { ex.string_lit("Hi"); });
+            });
+        });
+    }
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ div(id="intro") [ ] ]"#);
+        assert!(diagnostics.is_empty());
+        let attributes = first_element(&template).attributes();
+
+        // IncrementalDOM gets the attributes as statics, and skips the contents once rendered
+        let mut out = String::new();
+        write_view(&mut JsFormatter::new(&mut out, JsFormat::Minified), &INCREMENTAL_DOM, attributes);
+        assert_eq!(out, "function view(a){let b=IncrementalDOM.elementOpen(\"div\",\"incrust-static-0\",[\"id\",\"intro\"]);\
+            if(b.__incrust_static){IncrementalDOM.skip();}else{IncrementalDOM.text(\"Hi\");b.__incrust_static=true;}\
+            IncrementalDOM.elementClose(\"div\");}");
+
+        // The other backends render it each time
+        let mut out = String::new();
+        write_view(&mut JsFormatter::new(&mut out, JsFormat::Minified), &DOM, attributes);
+        assert_eq!(out, "function view(a){IncrustDom.elementOpen(\"div\",null,null,\"id\",\"intro\");IncrustDom.text(\"Hi\");IncrustDom.elementClose(\"div\");}");
+
+        // Generated keys can't be given to other elements
+        let (_, diagnostics) = parse_template_source(ecx, r#"view root [ p(key="incrust-static-0") [ ] ]"#);
+        assert_eq!(diagnostics.len(), 1);
+    });
+}
+
+#[test]
+fn test_element_key() {
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions};
+    use output_actions::IntoOutputActions;
+    use testing::{with_ecx, parse_template_source, first_element};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ li(key=item.id, class="row") [ ] ]"#);
+        assert!(diagnostics.is_empty());
+        let element = first_element(&template);
+
+        // The key goes to `elementOpen`, and is not rendered
+        let mut out = String::new();
+        JsFormatter::new(&mut out, JsFormat::Minified).function("view", vec![], &|js| {
+            INCREMENTAL_DOM.element_open(js, "li", element.attributes());
+        });
+        assert_eq!(out, "function view(){IncrementalDOM.elementOpen(\"li\",item.id,null,\"class\",\"row\");}");
+        assert_eq!(element.into_output_actions()[0].static_html(), Some("<li class=\"row\">".to_owned()));
+    });
+}
@@ -1,21 +1,25 @@
 
-use std::fmt::Write;
+use syntax::codemap::Span;
+use js_backend::JsBackend;
+use source_map::span_marker;
 
+/// Request the object write itself out as JS statements, rendering through the given backend
 pub trait WriteJs {
-    fn write_js(&self, js: &mut JsWrite);
+    fn write_js(&self, js: &mut JsWrite, backend: &JsBackend);
 }
 
-/// Request the object write itself out as a series of named Javascript functions
+/// Request the object write itself out as a series of named Javascript functions, with views
+/// rendering through the given backend
 pub trait WriteJsFunctions {
-    fn write_js_functions(&self, js: &mut JsWriteFunctions);
+    fn write_js_functions(&self, js: &mut JsWriteFunctions, backend: &JsBackend);
 }
 
 /// Implicit implementation of WriteJsFunctions for a Vec<&WriteJsFunctions>.
 /// Simply writes out the functions for each element in the vector.
 impl <T: WriteJsFunctions> WriteJsFunctions for Vec<T> {
-    fn write_js_functions(&self, js: &mut JsWriteFunctions) {
+    fn write_js_functions(&self, js: &mut JsWriteFunctions, backend: &JsBackend) {
         for element in self {
-            element.write_js_functions(js);
+            element.write_js_functions(js, backend);
         }
     }
 }
@@ -32,16 +36,66 @@ pub trait WriteJsSwitchBody {
 pub trait JsWrite {
     fn function(&mut self, func_name: &str, f: &Fn(&mut JsWrite));
 
-    fn let_statement(&mut self, var_name: &str, f: &FnOnce(&mut JsWriteSimpleExpr));
+    /// Declare a variable local to the enclosing block: `let name = value;`
+    fn let_statement(&mut self, var_name: &str, f: &Fn(&mut JsWriteSimpleExpr));
     fn call_method(&mut self, method_name: &str, f: &Fn(&mut JsWriteParamList));
     //fn write_simple_expr<F>(&mut self, f: F) where F: FnOnce(&mut JsWriteSimpleExpr);
 
+    fn assign(&mut self, var_name: &str, f: &Fn(&mut JsWriteSimpleExpr));
+    fn return_expr(&mut self, f: &Fn(&mut JsWriteSimpleExpr));
+
+    /// Run the statements in `f` only when the variable is undefined, as for initial store state
+    fn if_undefined(&mut self, var_name: &str, f: &Fn(&mut JsWrite));
+
+    fn if_else(&mut self, condition: &Fn(&mut JsWriteSimpleExpr), then: &Fn(&mut JsWrite), otherwise: &Fn(&mut JsWrite));
+
     /// Switch expression where the value to match is a simple variable reference
     fn switch_expr_simple(&mut self, var_name: &str, f: &Fn(&mut JsWriteSwitchBody));
+
+    /// Switch statement with a block of statements for each case
+    fn switch_cases(&mut self, value: &Fn(&mut JsWriteSimpleExpr), f: &Fn(&mut JsWriteSwitchCases));
+
+    /// Block of statements, with the variables declared in it only visible inside
+    fn block(&mut self, f: &Fn(&mut JsWrite));
+
+    /// Record that the statements written next were generated from the template source at `span`
+    fn source_span(&mut self, span: Span);
 }
 
 pub trait JsWriteFunctions {
     fn function(&mut self, func_name: &str, args: Vec<&str>, f: &Fn(&mut JsWrite));
+
+    /// Write source as is, such as a bundled runtime
+    fn source(&mut self, source: &str);
+
+    /// ES module import, e.g. `* as Redux` from `redux`
+    fn import_module(&mut self, binding: &str, module: &str);
+}
+
+/// Writes each function as an ES module export
+pub struct ExportFunctions<'w> {
+    funcs: &'w mut JsWriteFunctions
+}
+
+impl<'w> ExportFunctions<'w> {
+    pub fn new(funcs: &'w mut JsWriteFunctions) -> ExportFunctions<'w> {
+        ExportFunctions { funcs: funcs }
+    }
+}
+
+impl<'w> JsWriteFunctions for ExportFunctions<'w> {
+    fn function(&mut self, func_name: &str, args: Vec<&str>, f: &Fn(&mut JsWrite)) {
+        self.funcs.source("export ");
+        self.funcs.function(func_name, args, f);
+    }
+
+    fn source(&mut self, source: &str) {
+        self.funcs.source(source);
+    }
+
+    fn import_module(&mut self, binding: &str, module: &str) {
+        self.funcs.import_module(binding, module);
+    }
 }
 
 pub trait JsWriteSimpleExpr {
@@ -58,6 +112,25 @@ pub trait JsWriteSimpleExpr {
 
     fn binop_plus(&mut self);
     fn binop_minus(&mut self);
+
+    /// Call a function with the given variables as arguments
+    fn call(&mut self, func_name: &str, args: &[&str]);
+    /// Call a function with arguments written as expressions
+    fn call_params(&mut self, func_name: &str, f: &Fn(&mut JsWriteParamList));
+    /// Array literal of the elements written as parameters: `[a, b]`
+    fn array(&mut self, f: &Fn(&mut JsWriteParamList));
+    fn object(&mut self, f: &Fn(&mut JsWriteObjectMembers));
+    /// Function expression, e.g. an event handler: `function(event) { ... }`
+    fn function_expr(&mut self, args: Vec<&str>, f: &Fn(&mut JsWrite));
+    /// `(condition ? then : otherwise)`
+    fn conditional(&mut self, condition: &Fn(&mut JsWriteSimpleExpr), then: &Fn(&mut JsWriteSimpleExpr), otherwise: &Fn(&mut JsWriteSimpleExpr));
+
+    /// Record that the expression written next was generated from the template source at `span`
+    fn source_span(&mut self, span: Span);
+}
+
+pub trait JsWriteObjectMembers {
+    fn member(&mut self, name: &str, f: &Fn(&mut JsWriteSimpleExpr));
 }
 
 /// Allow writing switch case labels in a simplified expression syntax. This supports the Redux use case.
@@ -66,6 +139,12 @@ pub trait JsWriteSwitchBody {
     fn default_case(&mut self, f: &Fn(&mut JsWriteSimpleExpr));
 }
 
+/// Cases of a switch statement, each running a block of statements
+pub trait JsWriteSwitchCases {
+    /// Case for the string `case_str`, or the default case if `None`. Variables declared in the block are local to it.
+    fn case_block(&mut self, case_str: Option<&str>, f: &Fn(&mut JsWrite));
+}
+
 pub trait JsWriteFuncParamList {
     fn param(&mut self, var_name: &str);
 }
@@ -74,113 +153,512 @@ pub trait JsWriteParamList {
     fn param(&mut self, f: &Fn(&mut JsWriteSimpleExpr));
 }
 
-impl<T: Write> JsWrite for T {
-    fn let_statement(&mut self, var_name: &str, f: &FnOnce(&mut JsWriteSimpleExpr)) {
+/// Layout of the generated JS
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsFormat {
+    /// Indented, one statement per line, for debugging (the default)
+    Pretty,
+    /// No whitespace beyond what is required, with identifiers local to generated functions shortened
+    Minified
+}
+
+impl JsFormat {
+    pub fn from_name(name: &str) -> Option<JsFormat> {
+        match name {
+            "pretty" => Some(JsFormat::Pretty),
+            "minified" => Some(JsFormat::Minified),
+            _ => None
+        }
+    }
+}
+
+impl Default for JsFormat {
+    fn default() -> JsFormat { JsFormat::Pretty }
+}
+
+/// Names which can't be given to locals: JS reserved words (including those of strict mode and
+/// ES modules), literals, and names with special meaning in functions. Short names are only
+/// lowercase letters, so this holds every word they can collide with.
+const RESERVED_SHORT_NAMES: &'static [&'static str] = &[
+    "arguments", "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default",
+    "delete", "do", "else", "enum", "eval", "export", "extends", "false", "finally", "for", "function",
+    "if", "implements", "import", "in", "instanceof", "interface", "let", "new", "null", "package",
+    "private", "protected", "public", "return", "static", "super", "switch", "this", "throw", "true",
+    "try", "typeof", "undefined", "var", "void", "while", "with", "yield"
+];
+
+/// Writes JS in the given format. Keeps track of the indentation, and in minified output
+/// of the short names given to the parameters and variables of the function being written.
+/// Writes into a `String`, which can't fail, so the writing methods don't return errors.
+pub struct JsFormatter<'w> {
+    out: &'w mut String,
+    format: JsFormat,
+    indent: usize,
+    /// No line break is needed before the next statement, at the start or after `export `
+    at_line_start: bool,
+    params: usize,
+    locals: Vec<(String, String)>,
+    /// Index of the next short name, which is not reused within the function even after its local goes out of scope
+    next_local: usize,
+    /// Template source of the next statement, marked once the statement starts on its line
+    pending_span: Option<Span>
+}
+
+impl<'w> JsFormatter<'w> {
+    pub fn new(out: &'w mut String, format: JsFormat) -> JsFormatter<'w> {
+        JsFormatter {
+            out: out,
+            format: format,
+            indent: 0,
+            at_line_start: true,
+            params: 0,
+            locals: vec![],
+            next_local: 0,
+            pending_span: None
+        }
+    }
+
+    fn token(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    /// Whitespace only written in pretty output
+    fn space(&mut self) {
+        if self.format == JsFormat::Pretty {
+            self.token(" ");
+        }
+    }
+
+    /// Start a statement on a new line at the current indentation
+    fn statement(&mut self) {
+        if self.format == JsFormat::Pretty {
+            if !self.at_line_start {
+                self.token("\n");
+            }
+            for _ in 0..self.indent {
+                self.token("    ");
+            }
+        }
+        self.at_line_start = false;
+
+        if let Some(span) = self.pending_span.take() {
+            self.token(&span_marker(span));
+        }
+    }
+
+    fn open_block(&mut self) {
+        self.space();
+        self.token("{");
+        self.indent += 1;
+    }
+
+    fn close_block(&mut self) {
+        self.indent -= 1;
+        self.statement();
+        self.token("}");
+    }
+
+    /// Declare a parameter or variable of the function being written, returning the name to use for it.
+    /// Pretty output keeps the name, with a suffix if it shadows a local in scope, as the value of
+    /// `let data = data.profile;` would refer to the new `data` before it is initialized.
+    fn declare_local(&mut self, name: &str) -> String {
+        if self.format == JsFormat::Pretty {
+            let mut local_name = name.to_owned();
+            let mut n = 1;
+            while self.locals.iter().any(|&(_, ref declared)| *declared == local_name) {
+                local_name = format!("{}_{}", name, n);
+                n += 1;
+            }
+            self.locals.push((name.to_owned(), local_name.clone()));
+            return local_name;
+        }
+
+        let mut n = self.next_local;
+        while RESERVED_SHORT_NAMES.contains(&short_name(n).as_str()) {
+            n += 1;
+        }
+        self.next_local = n + 1;
+        let local_name = short_name(n);
+        self.locals.push((name.to_owned(), local_name.to_owned()));
+        local_name
+    }
+
+    /// Reference to a variable or a path such as `state.counter`, with the root renamed if it is local
+    fn reference(&self, path: &str) -> String {
+        let (root, rest) = match path.find('.') {
+            Some(pos) => (&path[..pos], &path[pos..]),
+            None => (path, "")
+        };
+
+        match self.locals.iter().rev().find(|&&(ref name, _)| name == root) {
+            Some(&(_, ref short_name)) => format!("{}{}", short_name, rest),
+            None => path.to_owned()
+        }
+    }
+}
+
+/// Short identifier for the `n`th local: `a` to `z`, then `aa`, `ab` and so on
+fn short_name(n: usize) -> String {
+    let letters = b"abcdefghijklmnopqrstuvwxyz";
+    let mut n = n;
+    let mut name = vec![letters[n % 26]];
+    while n >= 26 {
+        n = n / 26 - 1;
+        name.insert(0, letters[n % 26]);
+    }
+    String::from_utf8(name).unwrap()
+}
+
+impl<'w> JsWrite for JsFormatter<'w> {
+    fn let_statement(&mut self, var_name: &str, f: &Fn(&mut JsWriteSimpleExpr)) {
+        self.statement();
+        self.token("let ");
+        // The value may refer to an outer variable of the same name, so declare after writing it
+        let mut value = String::new();
+        self.next_local = {
+            let mut value_js = JsFormatter { out: &mut value, format: self.format, indent: 0, at_line_start: false, params: 0,
+                locals: self.locals.clone(), next_local: self.next_local, pending_span: None };
+            f(&mut value_js);
+            value_js.next_local
+        };
+        let local_name = self.declare_local(var_name);
+        self.token(&local_name);
+        self.space();
+        self.token("=");
+        self.space();
+        self.token(&value);
+        self.token(";");
+    }
+
+    fn assign(&mut self, var_name: &str, f: &Fn(&mut JsWriteSimpleExpr)) {
+        self.statement();
+        let var_name = self.reference(var_name);
+        self.token(&var_name);
+        self.space();
+        self.token("=");
+        self.space();
+        f(self);
+        self.token(";");
+    }
+
+    fn return_expr(&mut self, f: &Fn(&mut JsWriteSimpleExpr)) {
+        self.statement();
+        self.token("return ");
+        f(self);
+        self.token(";");
+    }
+
+    fn if_undefined(&mut self, var_name: &str, f: &Fn(&mut JsWrite)) {
+        self.statement();
+        let var_name = self.reference(var_name);
+        self.token("if");
+        self.space();
+        self.token(&format!("({} === undefined)", var_name));
+        self.open_block();
+        f(self);
+        self.close_block();
+    }
+
+    fn if_else(&mut self, condition: &Fn(&mut JsWriteSimpleExpr), then: &Fn(&mut JsWrite), otherwise: &Fn(&mut JsWrite)) {
+        self.statement();
+        self.token("if");
+        self.space();
+        self.token("(");
+        condition(self);
+        self.token(")");
+        self.open_block();
+        then(self);
+        self.close_block();
+        self.space();
+        self.token("else");
+        self.open_block();
+        otherwise(self);
+        self.close_block();
     }
 
     fn function(&mut self, func_name: &str, f: &Fn(&mut JsWrite)) {
-        write!(self, "function {}() {{", func_name);
+        self.statement();
+        self.token(&format!("function {}()", func_name));
+        self.open_block();
         f(self);
-        write!(self, "}};");
+        self.close_block();
     }
 
     fn call_method(&mut self, method_name: &str, f: &Fn(&mut  JsWriteParamList)) {
-        write!(self, "{}(", method_name);
+        self.statement();
+        let method_name = self.reference(method_name);
+        self.token(&method_name);
+        self.token("(");
+        self.params = 0;
         f(self);
-        write!(self, ");\r\n");
+        self.token(");");
     }
 
-    /*
-    fn write_simple_expr<F>(&mut self, f: F) where F: FnOnce(&mut JsWriteSimpleExpr) {
+    fn switch_expr_simple(&mut self, var_name: &str, f: &Fn(&mut JsWriteSwitchBody)) {
+        self.statement();
+        let var_name = self.reference(var_name);
+        self.token("switch");
+        self.space();
+        self.token(&format!("({})", var_name));
+        self.open_block();
+        f(self);
+        self.close_block();
     }
-    */
 
-    fn switch_expr_simple(&mut self, var_name: &str, f: &Fn(&mut JsWriteSwitchBody)) {
-        write!(self, "switch ({}) {{", &var_name);
+    fn switch_cases(&mut self, value: &Fn(&mut JsWriteSimpleExpr), f: &Fn(&mut JsWriteSwitchCases)) {
+        self.statement();
+        self.token("switch");
+        self.space();
+        self.token("(");
+        value(self);
+        self.token(")");
+        self.open_block();
         f(self);
-        write!(self, "}};");
+        self.close_block();
+    }
+
+    fn block(&mut self, f: &Fn(&mut JsWrite)) {
+        self.statement();
+        self.token("{");
+        self.indent += 1;
+        let locals = self.locals.len();
+        f(self);
+        self.locals.truncate(locals);
+        self.close_block();
+    }
+
+    /// The span is marked at the start of the next statement, after its line break and indentation
+    fn source_span(&mut self, span: Span) {
+        self.pending_span = Some(span);
+    }
+}
+
+impl<'w> JsWriteSwitchCases for JsFormatter<'w> {
+    fn case_block(&mut self, case_str: Option<&str>, f: &Fn(&mut JsWrite)) {
+        self.statement();
+        match case_str {
+            Some(case_str) => { self.token(&format!("case '{}':", case_str)); },
+            None => { self.token("default:"); }
+        };
+        self.open_block();
+        let locals = self.locals.len();
+        f(self);
+        self.locals.truncate(locals);
+        self.statement();
+        self.token("break;");
+        self.close_block();
     }
 }
 
-impl<T: Write> JsWriteSwitchBody for T {
+impl<'w> JsWriteSwitchBody for JsFormatter<'w> {
     fn case_str(&mut self, case_str: &str, f: &Fn(&mut JsWriteSimpleExpr)) {
-        write!(self, "case '{}': return", &case_str);
+        self.statement();
+        self.token(&format!("case '{}':", case_str));
+        self.space();
+        self.token("return ");
         f(self);
-        write!(self, ";");
+        self.token(";");
     }
 
     fn default_case(&mut self, f: &Fn(&mut JsWriteSimpleExpr)) {
-        write!(self, "default: return ");
+        self.statement();
+        self.token("default:");
+        self.space();
+        self.token("return ");
         f(self);
-        write!(self, ";");
+        self.token(";");
     }
 }
 
-impl<T: Write> JsWriteFunctions for T {
+impl<'w> JsWriteFunctions for JsFormatter<'w> {
     fn function(&mut self, func_name: &str, args: Vec<&str>, f: &Fn(&mut JsWrite)) {
-        let args_str = &args.join(", ");
-        write!(self, "function {}({}) {{ ", func_name, &args_str);
+        self.locals.clear();
+        self.next_local = 0;
+        let args: Vec<String> = args.iter().map(|arg| self.declare_local(arg)).collect();
+        let separator = if self.format == JsFormat::Pretty { ", " } else { "," };
+
+        self.statement();
+        self.token(&format!("function {}({})", func_name, args.join(separator)));
+        self.open_block();
         f(self);
-        write!(self, "}};");
+        self.close_block();
+    }
+
+    fn source(&mut self, source: &str) {
+        self.statement();
+        self.token(source);
+        self.at_line_start = true;
+    }
+
+    fn import_module(&mut self, binding: &str, module: &str) {
+        self.statement();
+        self.token(&format!("import {} from '{}';", binding, module));
     }
 }
 
-impl<T: Write> JsWriteSimpleExpr for T {
+impl<'w> JsWriteSimpleExpr for JsFormatter<'w> {
     fn var_reference(&mut self, var_name: &str) {
-        write!(self, "{}", var_name);
+        let reference = self.reference(var_name);
+        self.token(&reference);
     }
 
     fn string_lit(&mut self, lit: &str) {
-        write!(self, "\"{}\"", lit);
+        let mut escaped = String::with_capacity(lit.len() + 2);
+        escaped.push('"');
+        for c in lit.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c => escaped.push(c)
+            }
+        }
+        escaped.push('"');
+        self.token(&escaped);
     }
 
     fn int32_lit(&mut self, n: i32) {
-        write!(self, "{}", n);
+        self.int64_lit(n as i64);
     }
 
     fn int64_lit(&mut self, n: i64) {
-        write!(self, "{}", n);
+        // `a - -1` would become `a--1` without spaces
+        if n < 0 && self.format == JsFormat::Minified {
+            self.token(&format!("({})", n));
+        } else {
+            self.token(&n.to_string());
+        }
     }
 
     fn open_brace(&mut self) {
-        write!(self, "{{");
+        self.token("{");
     }
 
     fn close_brace(&mut self) {
-        write!(self, "}}");
+        self.token("}");
     }
 
     fn open_paren(&mut self) {
-        write!(self, "(");
+        self.token("(");
     }
 
     fn close_paren(&mut self) {
-        write!(self, ")");
+        self.token(")");
     }
 
     fn binop_plus(&mut self) {
-        write!(self, " + ");
+        self.space();
+        self.token("+");
+        self.space();
     }
 
     fn binop_minus(&mut self) {
-        write!(self, " - ");
+        self.space();
+        self.token("-");
+        self.space();
+    }
+
+    fn call(&mut self, func_name: &str, args: &[&str]) {
+        let args: Vec<String> = args.iter().map(|arg| self.reference(arg)).collect();
+        let separator = if self.format == JsFormat::Pretty { ", " } else { "," };
+        self.token(&format!("{}({})", func_name, args.join(separator)));
+    }
+
+    fn call_params(&mut self, func_name: &str, f: &Fn(&mut JsWriteParamList)) {
+        // The call may be an argument itself
+        let params = self.params;
+        self.token(func_name);
+        self.token("(");
+        self.params = 0;
+        f(self);
+        self.token(")");
+        self.params = params;
+    }
+
+    fn array(&mut self, f: &Fn(&mut JsWriteParamList)) {
+        // The array may be an argument itself
+        let params = self.params;
+        self.token("[");
+        self.params = 0;
+        f(self);
+        self.token("]");
+        self.params = params;
+    }
+
+    fn function_expr(&mut self, args: Vec<&str>, f: &Fn(&mut JsWrite)) {
+        // The arguments are only visible within the function, which may be an argument itself
+        let (locals, params) = (self.locals.len(), self.params);
+        let args: Vec<String> = args.iter().map(|arg| self.declare_local(arg)).collect();
+        let separator = if self.format == JsFormat::Pretty { ", " } else { "," };
+
+        self.token(&format!("function({})", args.join(separator)));
+        self.open_block();
+        f(self);
+        self.close_block();
+        self.locals.truncate(locals);
+        self.params = params;
+    }
+
+    fn conditional(&mut self, condition: &Fn(&mut JsWriteSimpleExpr), then: &Fn(&mut JsWriteSimpleExpr), otherwise: &Fn(&mut JsWriteSimpleExpr)) {
+        self.token("(");
+        condition(self);
+        self.space();
+        self.token("?");
+        self.space();
+        then(self);
+        self.space();
+        self.token(":");
+        self.space();
+        otherwise(self);
+        self.token(")");
+    }
+
+    fn object(&mut self, f: &Fn(&mut JsWriteObjectMembers)) {
+        self.token("{");
+        self.space();
+        f(self);
+        self.token("}");
+    }
+
+    fn source_span(&mut self, span: Span) {
+        self.token(&span_marker(span));
+    }
+}
+
+impl<'w> JsWriteObjectMembers for JsFormatter<'w> {
+    fn member(&mut self, name: &str, f: &Fn(&mut JsWriteSimpleExpr)) {
+        self.token(name);
+        self.token(":");
+        self.space();
+        f(self);
+        self.token(",");
+        self.space();
     }
 }
 
-impl<T: Write> JsWriteParamList for T {
+impl<'w> JsWriteParamList for JsFormatter<'w> {
     fn param(&mut self, f: &Fn(&mut JsWriteSimpleExpr)) {
+        if self.params > 0 {
+            self.token(",");
+            self.space();
+        }
+        self.params += 1;
         f(self);
     }
 }
 
-mod output_strings {
-    use super::{WriteJs, WriteJsFunctions};
+pub mod output_strings {
+    use super::{WriteJs, WriteJsFunctions, ExportFunctions, JsFormatter};
     use std::iter::Iterator;
     use syntax::codemap::{Span, DUMMY_SP};
     use syntax::ext::base::ExtCtxt;
     use syntax::ast;
     use codegen::lang::{Lang, Html, Js};
     use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
+    use codegen::named_output::{NamedOutput, NamedOutputType};
+    use config::{Config, JsModuleFormat};
+    use source_map::strip_span_markers;
 
     /*
     impl<S: WriteJs> WriteOutputStrings<Js> for S {
@@ -192,18 +670,126 @@ mod output_strings {
     }
     */
 
-    impl<S: WriteJsFunctions> WriteOutputStrings<Js> for S {
+    /// Write the functions in the configured format and backend, returning the JS and the template spans marked in it
+    pub fn js_functions_output(functions: &WriteJsFunctions, config: &Config) -> (String, Vec<(usize, Span)>) {
+        let backend = config.js_backend.backend();
+        let mut out = String::new();
+        {
+            let mut js = JsFormatter::new(&mut out, config.js_format);
+            match config.js_module {
+                JsModuleFormat::Global => functions.write_js_functions(&mut js, backend),
+                JsModuleFormat::Es => functions.write_js_functions(&mut ExportFunctions::new(&mut js), backend)
+            };
+        }
+        strip_span_markers(&out)
+    }
+
+    /// JS output of a template item, written with the options of the template, e.g. `JsOutput::new(&root_store, &config)`
+    pub struct JsOutput<'a, S: 'a> {
+        functions: &'a S,
+        config: &'a Config
+    }
+
+    impl<'a, S: 'a> JsOutput<'a, S> {
+        pub fn new(functions: &'a S, config: &'a Config) -> JsOutput<'a, S> {
+            JsOutput { functions: functions, config: config }
+        }
+    }
+
+    impl<'a, S: WriteJsFunctions> WriteOutputStrings<Js> for JsOutput<'a, S> {
         fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Js>) {
-            ecx.span_warn(DUMMY_SP, &format!("Writing output strings for js functions"));
+            debug!("Writing output strings for js functions");
 
-            let mut out = String::new();
-            self.write_js_functions(&mut out);
-            w.write_output_string(ecx, &mut out);
+            let (out, _) = js_functions_output(self.functions, self.config);
+            w.write_output_string(ecx, &out);
         }
     }
+
+    impl<'a, S: NamedOutput<Js>> NamedOutput<Js> for JsOutput<'a, S> {
+        fn output_name(&self) -> &str { self.functions.output_name() }
+        fn output_type(&self) -> NamedOutputType { self.functions.output_type() }
+    }
 }
 
 #[test]
-fn test_jsWrite_from_Write() {
+fn test_js_formatter() {
+    fn write_reducer(funcs: &mut JsWriteFunctions) {
+        funcs.function("reducer", vec!["counter", "action"], &|js| {
+            js.switch_expr_simple("action.type", &|switch_body| {
+                switch_body.case_str("INCREMENT", &|js_simple| {
+                    js_simple.var_reference("counter");
+                    js_simple.binop_plus();
+                    js_simple.int32_lit(1);
+                });
+            });
+        });
+    }
+
+    let mut pretty = String::new();
+    write_reducer(&mut JsFormatter::new(&mut pretty, JsFormat::Pretty));
+    assert_eq!(pretty, "function reducer(counter, action) {\n    switch (action.type) {\n        case 'INCREMENT': return counter + 1;\n    }\n}");
+
+    let mut minified = String::new();
+    write_reducer(&mut JsFormatter::new(&mut minified, JsFormat::Minified));
+    assert_eq!(minified, "function reducer(a,b){switch(b.type){case 'INCREMENT':return a+1;}}");
+
+    assert_eq!(short_name(25), "z");
+    assert_eq!(short_name(26), "aa");
+}
+
+#[test]
+fn test_js_formatter_block() {
+    fn write_render(funcs: &mut JsWriteFunctions) {
+        funcs.function("render", vec!["data"], &|js| {
+            js.block(&|js| {
+                js.let_statement("city", &|ex| { ex.var_reference("data.address.city"); });
+                js.return_expr(&|ex| { ex.var_reference("city.name"); });
+            });
+            // No longer declared after the block
+            js.return_expr(&|ex| { ex.var_reference("city"); });
+        });
+    }
+
+    let mut minified = String::new();
+    write_render(&mut JsFormatter::new(&mut minified, JsFormat::Minified));
+    assert_eq!(minified, "function render(a){{let b=a.address.city;return b.name;}return city;}");
+}
 
-}
\ No newline at end of file
+#[test]
+fn test_js_formatter_shadowing() {
+    fn write_render(funcs: &mut JsWriteFunctions) {
+        funcs.function("render", vec!["data"], &|js| {
+            js.block(&|js| {
+                js.let_statement("data", &|ex| { ex.var_reference("data.profile"); });
+                js.return_expr(&|ex| { ex.var_reference("data.name"); });
+            });
+            js.return_expr(&|ex| { ex.var_reference("data"); });
+        });
+    }
+
+    // The inner `data` is renamed, as its value refers to the outer one
+    let mut pretty = String::new();
+    write_render(&mut JsFormatter::new(&mut pretty, JsFormat::Pretty));
+    assert_eq!(pretty, "function render(data) {\n    {\n        let data_1 = data.profile;\n        return data_1.name;\n    }\n    return data;\n}");
+
+    let mut minified = String::new();
+    write_render(&mut JsFormatter::new(&mut minified, JsFormat::Minified));
+    assert_eq!(minified, "function render(a){{let b=a.profile;return b.name;}return a;}");
+}
+
+#[test]
+fn test_js_formatter_short_names() {
+    // The 119th name would be the keyword `do`
+    let args: Vec<String> = (0..120).map(|n| format!("arg{}", n)).collect();
+    let mut minified = String::new();
+    JsWriteFunctions::function(&mut JsFormatter::new(&mut minified, JsFormat::Minified), "render", args.iter().map(|arg| arg.as_str()).collect(), &|_| {});
+    assert!(minified.ends_with(",dn,dp,dq){}"));
+
+    // No three letter keyword is given out either
+    let mut out = String::new();
+    let mut formatter = JsFormatter::new(&mut out, JsFormat::Minified);
+    let names: Vec<String> = (0..26 * 27 * 26).map(|n| formatter.declare_local(&format!("local{}", n))).collect();
+    for keyword in &["for", "let", "new", "try", "var"] {
+        assert!(!names.iter().any(|name| name == keyword), "`{}` was given to a local", keyword);
+    }
+}
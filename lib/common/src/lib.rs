@@ -8,6 +8,7 @@ extern crate syntax;
 extern crate rustc;
 extern crate rustc_plugin;
 extern crate itertools;
+extern crate futures;
 
 use rustc_plugin::Registry;
 
@@ -29,10 +30,27 @@ use syntax::parse::parser::Parser;
 use syntax::ptr::P;
 
 pub mod codegen;
+pub mod diagnostics;
+pub mod recovery;
 pub mod output_actions;
+pub mod optimize;
 pub mod simple_expr;
 pub mod js_write;
+pub mod js_backend;
+pub mod source_map;
+pub mod ts_write;
 pub mod nodes;
+pub mod types;
+pub mod typecheck;
+pub mod value;
+pub mod model;
+pub mod config;
+pub mod runtime;
+pub mod render;
+pub mod bundle;
+
+#[cfg(test)]
+mod testing;
 
 
 /*
@@ -0,0 +1,103 @@
+use value::Value;
+
+
+/// Member of a serialized enum holding the variant name, which views match on
+pub const ENUM_TAG: &'static str = "tag";
+
+/// Member of a serialized enum holding the values of a tuple variant, as an array
+pub const ENUM_VALUES: &'static str = "values";
+
+/// Implemented by `#[derive(IncrustModel)]` for Rust structs exposed to templates and JS
+pub trait IncrustModel {
+    /// Name of the model, as used for store state types in templates
+    fn model_name() -> &'static str;
+
+    /// Field names and Rust type names, in declaration order
+    fn model_fields() -> Vec<(&'static str, &'static str)>;
+
+    /// Variant names and the Rust type names of their values, for enums
+    fn model_variants() -> Vec<(&'static str, Vec<&'static str>)> { vec![] }
+
+    /// JS constructor function for the model, with an accessor for each field
+    fn js_factory() -> &'static str;
+
+    /// Serialize as JSON, used to hydrate the client side store
+    fn to_json(&self) -> String where Self: ToValue {
+        self.to_value().to_json()
+    }
+}
+
+/// Conversion into a dynamic value, for rendering and serialization
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value { self.clone() }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value { Value::String(self.clone()) }
+}
+
+impl<'s> ToValue for &'s str {
+    fn to_value(&self) -> Value { Value::String((*self).to_owned()) }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value { Value::Bool(*self) }
+}
+
+macro_rules! number_to_value (
+    ($($ty: ty),*) => (
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self) -> Value { Value::Number(*self as f64) }
+            }
+        )*
+    )
+);
+number_to_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        match self {
+            &Some(ref inner) => inner.to_value(),
+            &None => Value::Null
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|element| element.to_value()).collect())
+    }
+}
+
+/// Compile time registry of derived models, consulted when checking templates.
+/// Items are expanded in source order, so a derived model is only visible to templates after it
+/// in the crate (e.g. declared in a `mod models;` before the `template!`). Templates which can't rely
+/// on this order declare the model themselves with `model Name { ... }`.
+pub mod registry {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use nodes::model_node::Model;
+
+    thread_local! {
+        static MODELS: RefCell<HashMap<String, Model>> = RefCell::new(HashMap::new());
+    }
+
+    pub fn register_model(model: Model) {
+        MODELS.with(|models| {
+            models.borrow_mut().insert(model.name().to_owned(), model);
+        });
+    }
+
+    pub fn lookup_model(name: &str) -> Option<Model> {
+        MODELS.with(|models| models.borrow().get(name).cloned())
+    }
+
+    pub fn registered_models() -> Vec<Model> {
+        MODELS.with(|models| models.borrow().values().cloned().collect())
+    }
+}
@@ -1,5 +1,8 @@
 
+use syntax::codemap::Span;
 use super::element_node::Element;
+use super::match_node::MatchBlock;
+use super::let_node::LetBlock;
 use simple_expr::SimpleExpr;
 
 
@@ -9,6 +12,25 @@ pub enum ContentNode {
     ElementNode(Element),
     ExprNode(SimpleExpr),
     LiteralNode(LitValue),
+    /// Point after which the output rendered so far can be sent, e.g. after `head`
+    FlushNode(Span),
+    MatchNode(MatchBlock),
+    /// Contents with a local name for a value
+    LetNode(LetBlock),
+}
+
+impl ContentNode {
+    /// Mark expression references rooted at `Any` typed state as dynamic
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        match self {
+            &mut ContentNode::ElementNode(ref mut element) => element.resolve_dynamic_references(roots),
+            &mut ContentNode::ExprNode(ref mut simple_expr) => simple_expr.resolve_dynamic_references(roots),
+            &mut ContentNode::MatchNode(ref mut block) => block.resolve_dynamic_references(roots),
+            &mut ContentNode::LetNode(ref mut block) => block.resolve_dynamic_references(roots),
+            &mut ContentNode::LiteralNode(_) |
+            &mut ContentNode::FlushNode(_) => {}
+        }
+    }
 }
 
 /// Literal (static) value.
@@ -27,9 +49,14 @@ pub mod parse {
     use syntax::parse::{token, PResult};
     use syntax::parse::parser::Parser;
     use nodes::element_node::parse::parse_element;
+    use nodes::match_node::parse::parse_match;
+    use nodes::let_node::parse::parse_let;
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite, unexpected_token};
+    use recovery::{skip_until, skip_token_tree, at_element, at_flush, at_match, at_let};
 
     use simple_expr::SimpleExprToken;
-    use simple_expr::parse::parse_simple_expr;
+    use simple_expr::parse::{parse_simple_expr, parse_interpolated_expr};
+    use syntax::ast::LitKind;
 
     #[derive(Clone, Debug)]
     pub enum NodeType {
@@ -65,42 +92,244 @@ pub mod parse {
         Ok(ContentNode::ExprNode(simple_expr))
     }
 
-    pub fn parse_contents<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, node_type: &NodeType) -> PResult<'a, Vec<ContentNode>> {
+    /// Part of a text literal: static text, or the source of an interpolated `{expression}`
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum TextPart {
+        Text(String),
+        Expr(String)
+    }
+
+    /// Split text such as `"Counter: {counter}"` into its static and interpolated parts.
+    /// `{{` and `}}` are literal braces.
+    pub fn split_interpolation(text: &str) -> Result<Vec<TextPart>, &'static str> {
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => { chars.next(); current.push('{'); },
+                '}' if chars.peek() == Some(&'}') => { chars.next(); current.push('}'); },
+                '{' => {
+                    if !current.is_empty() {
+                        parts.push(TextPart::Text(current));
+                        current = String::new();
+                    }
+
+                    let mut expr = String::new();
+                    let mut closed = false;
+                    while let Some(c) = chars.next() {
+                        if c == '}' { closed = true; break; }
+                        expr.push(c);
+                    }
+
+                    if !closed {
+                        return Err("unclosed `{` in text, write `{{` for a literal brace");
+                    }
+                    if expr.trim().is_empty() {
+                        return Err("empty `{}` in text, write `{{}}` for literal braces");
+                    }
+                    parts.push(TextPart::Expr(expr.trim().to_owned()));
+                },
+                '}' => { return Err("unmatched `}` in text, write `}}` for a literal brace"); },
+                c => { current.push(c); }
+            }
+        }
+
+        if !current.is_empty() {
+            parts.push(TextPart::Text(current));
+        }
+        Ok(parts)
+    }
+
+    /// Parse a text literal into text nodes and the expressions interpolated into it
+    fn parse_text<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, diagnostics: &mut DiagnosticWrite) -> PResult<'a, Vec<ContentNode>> {
+        let lit = try!(parser.parse_lit());
+        let text = match lit.node {
+            LitKind::Str(ref s, _) => s.to_string(),
+            _ => {
+                diagnostics.write_diagnostic(Diagnostic::error(DiagnosticCode::UnsupportedLiteral, lit.span, "only text can be written in element contents")
+                    .help("write other values as an expression, e.g. `{ 1 }`"));
+                return Ok(vec![]);
+            }
+        };
+
+        let parts = match split_interpolation(&text) {
+            Ok(parts) => parts,
+            Err(message) => {
+                diagnostics.write_diagnostic(Diagnostic::error(DiagnosticCode::UnexpectedToken, lit.span, message));
+                return Ok(vec![]);
+            }
+        };
+
+        let nodes = parts.into_iter().filter_map(|part| match part {
+            TextPart::Text(contents) => Some(ContentNode::LiteralNode(LitValue::LitString(contents))),
+            TextPart::Expr(source) => parse_interpolated_expr(ecx, &source, lit.span, diagnostics).map(ContentNode::ExprNode)
+        }).collect();
+        Ok(nodes)
+    }
+
+    /// Parse element contents up to (but not including) the closing bracket.
+    /// Errors are collected and parsing resumes at the next element, so that one mistake
+    /// doesn't hide the rest.
+    pub fn parse_contents<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, node_type: &NodeType, diagnostics: &mut DiagnosticWrite) -> Vec<ContentNode> {
         let mut nodes: Vec<ContentNode> = Vec::new();
 
         loop {
-            ecx.span_warn(span, &format!("Parsing contents ({:?}) - token: {:?}", &node_type, &parser.token));
+            debug!("Parsing contents ({:?}) - token: {:?}", &node_type, &parser.token);
+
+            if at_flush(parser) {
+                nodes.push(ContentNode::FlushNode(parser.span));
+                parser.bump();
+                continue;
+            }
+
+            if at_match(parser) {
+                match parse_match(ecx, parser, span, diagnostics) {
+                    Ok(block) => { nodes.push(ContentNode::MatchNode(block)); },
+                    Err(err) => {
+                        diagnostics.write_parse_error(err);
+                        skip_until(parser, &|parser| at_element(parser) || at_match(parser) || at_let(parser));
+                    }
+                };
+                continue;
+            }
+
+            if at_let(parser) {
+                match parse_let(ecx, parser, span, diagnostics) {
+                    Ok(block) => { nodes.push(ContentNode::LetNode(block)); },
+                    Err(err) => {
+                        diagnostics.write_parse_error(err);
+                        skip_until(parser, &|parser| at_element(parser) || at_match(parser) || at_let(parser));
+                    }
+                };
+                continue;
+            }
 
             match parser.token {
                 token::CloseDelim(token::Bracket) => {
-                    ecx.span_warn(span, &format!("Parsing contents ({:?}) - complete", &node_type));
+                    debug!("Parsing contents ({:?}) - complete", &node_type);
                     break;
                 },
 
                 token::Ident(_) => {
-                    let element = try!(parse_element(ecx, parser, span, node_type));
-                    nodes.push(ContentNode::ElementNode(element));
+                    match parse_element(ecx, parser, span, node_type, diagnostics) {
+                        Ok(element) => {
+                            nodes.push(ContentNode::ElementNode(element));
+                            parser.bump();
+                        },
+                        Err(err) => {
+                            diagnostics.write_parse_error(err);
+                            skip_until(parser, &|parser| at_element(parser) || at_match(parser) || at_let(parser));
+                        }
+                    };
                 },
 
                 token::OpenDelim(token::Brace) => {
                     // Start of expression, which can be a literal value
-                    ecx.span_warn(span, "Parsing contents - got open expression");
-                    let node = try!(parse_simple_expr_or_lit_node(ecx, &mut parser, span));
-                    ecx.span_warn(span, &format!("Parsing contents - expression: {:?}", &node));
-                    nodes.push(node);
+                    debug!("Parsing contents - got open expression");
+                    match parse_simple_expr_or_lit_node(ecx, &mut parser, span) {
+                        Ok(node) => {
+                            debug!("Parsing contents - expression: {:?}", &node);
+                            nodes.push(node);
+                        },
+                        Err(err) => {
+                            diagnostics.write_parse_error(err);
+                            // Parentheses opened within the expression may still be open
+                            loop {
+                                skip_until(parser, &|_| false);
+                                if parser.token != token::CloseDelim(token::Paren) {
+                                    break;
+                                }
+                                parser.bump();
+                            }
+                        }
+                    };
+                    // Closing brace, which is missing if the expression ran into the end of the contents
+                    if parser.token == token::CloseDelim(token::Brace) {
+                        parser.bump();
+                    }
+                },
+
+                // Text, which may interpolate expressions
+                token::Literal(_, _) => {
+                    match parse_text(ecx, parser, diagnostics) {
+                        Ok(text_nodes) => { nodes.extend(text_nodes); },
+                        Err(err) => {
+                            diagnostics.write_parse_error(err);
+                            skip_token_tree(parser);
+                        }
+                    };
+                },
+
+                token::Eof | token::CloseDelim(_) => {
+                    diagnostics.write_parse_error(unexpected_token(parser, "element contents", "`]`"));
+                    break;
                 },
 
                 _ => {
-                    ecx.span_err(span, &format!("Parsing contents ({:?}) - unknown token: {:?}", &node_type, &parser.token));
+                    diagnostics.write_parse_error(unexpected_token(parser, "element contents", "an element, text, `{ expression }`, `match`, `let` or `]`"));
+                    skip_token_tree(parser);
                 }
             }
-            parser.bump();
         }
 
-        Ok(nodes)
+        nodes
     }
 }
 
+#[test]
+fn test_split_interpolation() {
+    use self::parse::{split_interpolation, TextPart};
+
+    assert_eq!(split_interpolation("Counter: {counter}!"), Ok(vec![
+        TextPart::Text("Counter: ".to_owned()),
+        TextPart::Expr("counter".to_owned()),
+        TextPart::Text("!".to_owned())
+    ]));
+    assert_eq!(split_interpolation("{{literal}}"), Ok(vec![TextPart::Text("{literal}".to_owned())]));
+    assert!(split_interpolation("Counter: {counter").is_err());
+}
+
+#[test]
+fn test_parse_contents_recovery() {
+    use testing::{with_ecx, parse_template_source, first_view};
+
+    with_ecx(|ecx| {
+        let source = r#"store counter { default => (0) }
+            view root [ div [ { (counter * 2) } ] p [ "{counter" ] span [ "ok" ] ]"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+
+        // Both mistakes are reported, and the elements after them are still parsed
+        assert_eq!(diagnostics.len(), 2);
+        let element_types: Vec<&str> = first_view(&template).nodes().iter()
+            .filter_map(|node| match node { &ContentNode::ElementNode(ref element) => Some(element.element_type()), _ => None })
+            .collect();
+        assert_eq!(element_types, vec!["div", "p", "span"]);
+    });
+}
+
+#[test]
+fn test_parse_flush() {
+    use testing::{with_ecx, parse_template_source, first_view};
+
+    with_ecx(|ecx| {
+        // Only a bare `flush` is a flush point; followed by attributes or contents it is an element
+        let source = r#"view root [ head [ ] flush flush(id="x") [ ] flush [ ] ]"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+
+        assert!(diagnostics.is_empty());
+        let kinds: Vec<&str> = first_view(&template).nodes().iter()
+            .map(|node| match node {
+                &ContentNode::FlushNode(_) => "flush point",
+                &ContentNode::ElementNode(ref element) => element.element_type(),
+                _ => "other"
+            })
+            .collect();
+        assert_eq!(kinds, vec!["head", "flush point", "flush", "flush"]);
+    });
+}
+
 pub mod output_ast {
     use super::ContentNode;
     use output_actions::{OutputAction, IntoOutputActions, WriteOutputActions, OutputActionWrite};
@@ -110,6 +339,9 @@ pub mod output_ast {
             match self {
                 &ContentNode::ElementNode(ref element) => element.into_output_actions(),
                 &ContentNode::LiteralNode(ref lit) => lit.into_output_actions(),
+                &ContentNode::FlushNode(_) => vec![OutputAction::Flush],
+                &ContentNode::MatchNode(ref block) => block.into_output_actions(),
+                &ContentNode::LetNode(ref block) => block.into_output_actions(),
                 &ContentNode::ExprNode(ref simple_expr) => {
                     // TODO: Return a WriteResult serializing simple_expr
                     vec![OutputAction::WriteResult(simple_expr.clone())]
@@ -129,6 +361,22 @@ pub mod output_ast {
                     lit.write_output_actions(w);
                 },
 
+                &ContentNode::FlushNode(_) => {
+                    w.write_output_action(&OutputAction::Flush);
+                },
+
+                &ContentNode::MatchNode(ref block) => {
+                    for output_action in &block.into_output_actions() {
+                        w.write_output_action(output_action);
+                    }
+                },
+
+                &ContentNode::LetNode(ref block) => {
+                    for output_action in &block.into_output_actions() {
+                        w.write_output_action(output_action);
+                    }
+                },
+
                 &ContentNode::ExprNode(ref simple_expr) => {
                     // TODO: Write a WriteResult serializing simple_expr
                     w.write_output_action(&OutputAction::WriteResult(simple_expr.clone()));
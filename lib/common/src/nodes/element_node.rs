@@ -1,61 +1,582 @@
 
 use syntax::codemap::Span;
 use super::content_node::ContentNode;
+use simple_expr::SimpleExpr;
 
 
+/// HTML elements which cannot have contents, written without a closing tag
+const VOID_ELEMENTS: &'static [&'static str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr"
+];
+
+pub fn is_void_element(element_type: &str) -> bool {
+    VOID_ELEMENTS.contains(&element_type)
+}
+
+/// HTML attributes which are true when present, whatever their value
+const BOOLEAN_ATTRIBUTES: &'static [&'static str] = &[
+    "allowfullscreen", "async", "autofocus", "autoplay", "checked", "controls", "default", "defer",
+    "disabled", "formnovalidate", "hidden", "ismap", "loop", "multiple", "muted", "novalidate",
+    "open", "readonly", "required", "reversed", "selected"
+];
+
+/// Attributes of form controls which only give the initial state, while the DOM property holds the current one
+pub const DOM_PROPERTIES: &'static [&'static str] = &["value", "checked", "selected"];
+
+/// How an attribute value is rendered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeKind {
+    /// `title="..."`, written with the value as text
+    Text,
+    /// `disabled`, written without a value while the condition is true and left out otherwise
+    Boolean,
+    /// `value`, written as text on the server and set as the property in the browser
+    Property,
+    /// `checked` and `selected`, written as boolean attributes on the server and set as properties in the browser
+    BooleanProperty
+}
+
+impl AttributeKind {
+    pub fn of(name: &str) -> AttributeKind {
+        match (BOOLEAN_ATTRIBUTES.contains(&name), DOM_PROPERTIES.contains(&name)) {
+            (true, true) => AttributeKind::BooleanProperty,
+            (true, false) => AttributeKind::Boolean,
+            (false, true) => AttributeKind::Property,
+            (false, false) => AttributeKind::Text
+        }
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        match *self {
+            AttributeKind::Boolean | AttributeKind::BooleanProperty => true,
+            _ => false
+        }
+    }
+}
+
+
+/// Attribute of an element, written within parentheses after the element type:
+/// `div(title="Counter", class:active=is_selected, style={ color: theme.fg }) [ ... ]`
+#[derive(Clone, Debug)]
+pub enum ElementAttribute {
+    /// `name=expr`
+    Value(String, SimpleExpr),
+    /// `class:name=condition`, adding the class while the condition is true
+    ClassToggle(String, SimpleExpr),
+    /// `style={ property: expr, ... }`
+    Style(Vec<(String, SimpleExpr)>),
+    /// `bind:value=store.field`, rendering the field and setting it from the input with a generated action
+    Bind(String, SimpleExpr),
+    /// `key=expr`, identifying the element among its siblings when patching, so it keeps its DOM state
+    /// (focus, input contents) when moved. Not written to the HTML.
+    Key(SimpleExpr),
+}
+
+impl ElementAttribute {
+    pub fn exprs(&self) -> Vec<&SimpleExpr> {
+        match self {
+            &ElementAttribute::Value(_, ref simple_expr) |
+            &ElementAttribute::ClassToggle(_, ref simple_expr) |
+            &ElementAttribute::Bind(_, ref simple_expr) |
+            &ElementAttribute::Key(ref simple_expr) => vec![simple_expr],
+            &ElementAttribute::Style(ref properties) => properties.iter().map(|&(_, ref simple_expr)| simple_expr).collect()
+        }
+    }
+
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        match self {
+            &mut ElementAttribute::Value(_, ref mut simple_expr) |
+            &mut ElementAttribute::ClassToggle(_, ref mut simple_expr) |
+            &mut ElementAttribute::Bind(_, ref mut simple_expr) |
+            &mut ElementAttribute::Key(ref mut simple_expr) => simple_expr.resolve_dynamic_references(roots),
+            &mut ElementAttribute::Style(ref mut properties) => {
+                for &mut (_, ref mut simple_expr) in properties {
+                    simple_expr.resolve_dynamic_references(roots);
+                }
+            }
+        }
+    }
+}
+
+/// Attributes in the order they are written: other attributes as given, boolean attributes, then a single `class`
+/// combining the `class` value with the toggled classes, and a single `style`
+struct AttributeGroups<'e> {
+    values: Vec<(&'e str, &'e SimpleExpr)>,
+    booleans: Vec<(&'e str, &'e SimpleExpr)>,
+    class: Option<&'e SimpleExpr>,
+    class_toggles: Vec<(&'e str, &'e SimpleExpr)>,
+    style: Vec<(&'e str, &'e SimpleExpr)>,
+    binds: Vec<(&'e str, &'e SimpleExpr)>
+}
+
+impl<'e> AttributeGroups<'e> {
+    fn new(attributes: &'e [ElementAttribute]) -> AttributeGroups<'e> {
+        let mut groups = AttributeGroups { values: vec![], booleans: vec![], class: None, class_toggles: vec![], style: vec![], binds: vec![] };
+        for attribute in attributes {
+            match attribute {
+                &ElementAttribute::Value(ref name, ref simple_expr) if name == "class" => { groups.class = Some(simple_expr); },
+                &ElementAttribute::Value(ref name, ref simple_expr) if AttributeKind::of(name).is_boolean() => { groups.booleans.push((name.as_str(), simple_expr)); },
+                &ElementAttribute::Value(ref name, ref simple_expr) => { groups.values.push((name.as_str(), simple_expr)); },
+                &ElementAttribute::ClassToggle(ref class_name, ref condition) => { groups.class_toggles.push((class_name.as_str(), condition)); },
+                &ElementAttribute::Style(ref properties) => {
+                    groups.style.extend(properties.iter().map(|&(ref property, ref simple_expr)| (property.as_str(), simple_expr)));
+                },
+                &ElementAttribute::Bind(ref property, ref path) => { groups.binds.push((property.as_str(), path)); },
+                &ElementAttribute::Key(_) => {}
+            }
+        }
+        groups
+    }
+
+    /// Toggled class name as appended to the class attribute, separated from any class before it
+    fn toggled_class(&self, idx: usize) -> String {
+        let (class_name, _) = self.class_toggles[idx];
+        if self.class.is_some() || idx > 0 { format!(" {}", class_name) } else { class_name.to_owned() }
+    }
+}
+
+/// Expression given with `key=`, if any
+pub fn element_key(attributes: &[ElementAttribute]) -> Option<&SimpleExpr> {
+    attributes.iter().filter_map(|attribute| match attribute {
+        &ElementAttribute::Key(ref simple_expr) => Some(simple_expr),
+        _ => None
+    }).next()
+}
+
 #[derive(Clone, Debug)]
 pub struct Element {
     element_type: String,
     span: Span,
+    attributes: Vec<ElementAttribute>,
     nodes: Vec<ContentNode>,
 }
 
+impl Element {
+    pub fn element_type(&self) -> &str {
+        &self.element_type
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn attributes(&self) -> &[ElementAttribute] {
+        &self.attributes
+    }
+
+    pub fn nodes(&self) -> &[ContentNode] {
+        &self.nodes
+    }
+
+    pub fn is_void(&self) -> bool {
+        is_void_element(&self.element_type)
+    }
+
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        for attribute in &mut self.attributes {
+            attribute.resolve_dynamic_references(roots);
+        }
+        for node in &mut self.nodes {
+            node.resolve_dynamic_references(roots);
+        }
+    }
+}
+
 pub mod parse {
-    use super::Element;
+    use super::{Element, ElementAttribute, AttributeKind, element_key};
+    use std::cell::Cell;
     use syntax::tokenstream::TokenTree;
     use syntax::codemap::Span;
     use syntax::ext::base::ExtCtxt;
     use syntax::parse::{token, PResult};
     use syntax::parse::parser::Parser;
 
+    use super::is_void_element;
     use nodes::content_node::parse::{NodeType, parse_contents};
+    use simple_expr::SimpleExpr;
+    use simple_expr::parse::parse_simple_expr_until;
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite, unexpected_token};
+    use output_actions::STATIC_KEY_PREFIX;
+
+    /// Attribute or style property name, which may be a keyword (`type`) or contain dashes (`font-size`)
+    fn parse_attribute_name<'a>(parser: &mut Parser<'a>) -> PResult<'a, String> {
+        let mut name = String::new();
+        loop {
+            match parser.token {
+                token::Ident(ident) => { name.push_str(&ident.name.to_string()); },
+                _ => { return Err(unexpected_token(parser, "element attributes", "an attribute name")); }
+            };
+            parser.bump();
+
+            if parser.token != token::BinOp(token::Minus) {
+                return Ok(name);
+            }
+            parser.bump();
+            name.push('-');
+        }
+    }
+
+    /// Expression up to the `,` or closing `end_delim` ending it, allowing parentheses within it
+    fn parse_attribute_value<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, span: Span, end_delim: token::DelimToken) -> PResult<'a, SimpleExpr> {
+        let depth = Cell::new(0);
+        let simple_expr = try!(parse_simple_expr_until(ecx, parser, span, &|token| {
+            match *token {
+                token::OpenDelim(token::Paren) => { depth.set(depth.get() + 1); false },
+                token::CloseDelim(token::Paren) if depth.get() > 0 => { depth.set(depth.get() - 1); false },
+                token::Comma => depth.get() == 0,
+                token::CloseDelim(delim) => delim == end_delim,
+                token::Eof => true,
+                _ => false
+            }
+        }));
+
+        if simple_expr.tokens().is_empty() {
+            return Err(unexpected_token(parser, "element attributes", "an attribute value"));
+        }
+        Ok(simple_expr)
+    }
+
+    /// `{ property: expr, ... }`
+    fn parse_style<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, span: Span) -> PResult<'a, Vec<(String, SimpleExpr)>> {
+        let mut properties = Vec::new();
+        try!(parser.expect(&token::OpenDelim(token::Brace)));
+        while parser.token != token::CloseDelim(token::Brace) {
+            let property = try!(parse_attribute_name(parser));
+            try!(parser.expect(&token::Colon));
+            let value = try!(parse_attribute_value(ecx, parser, span, token::Brace));
+            properties.push((property, value));
+
+            if !parser.eat(&token::Comma) {
+                break;
+            }
+        }
+        try!(parser.expect(&token::CloseDelim(token::Brace)));
+        Ok(properties)
+    }
+
+    /// `bind:property=path`, where the path is a store or a field of a store, which the generated action sets
+    fn bind_attribute(property: &str, path: SimpleExpr, span: Span, diagnostics: &mut DiagnosticWrite) -> Option<ElementAttribute> {
+        if property != "value" {
+            diagnostics.write_diagnostic(
+                Diagnostic::error(DiagnosticCode::InvalidAttribute, span, &format!("cannot bind `{}`", property))
+                    .help("only `bind:value` is supported"));
+            return None;
+        }
+
+        let bindable = path.reference_path().map_or(false, |path| path.split('.').count() <= 2);
+        if !bindable {
+            diagnostics.write_diagnostic(
+                Diagnostic::error(DiagnosticCode::InvalidAttribute, path.span(), "`bind:value` must name a store or a field of a store")
+                    .help("e.g. `bind:value=person.first_name`"));
+            return None;
+        }
+
+        Some(ElementAttribute::Bind(property.to_owned(), path))
+    }
+
+    /// Attributes within parentheses, including the closing parenthesis
+    fn parse_attributes<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, span: Span, diagnostics: &mut DiagnosticWrite) -> PResult<'a, Vec<ElementAttribute>> {
+        let mut attributes = Vec::new();
+        try!(parser.expect(&token::OpenDelim(token::Paren)));
+
+        while parser.token != token::CloseDelim(token::Paren) {
+            let attribute_span = parser.span;
+            let name = try!(parse_attribute_name(parser));
+
+            if parser.eat(&token::Colon) {
+                let class_name = try!(parse_attribute_name(parser));
+                try!(parser.expect(&token::Eq));
+                let condition = try!(parse_attribute_value(ecx, parser, span, token::Paren));
 
-    pub fn parse_element<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, node_type: &NodeType) -> PResult<'a, Element> {
+                if name == "bind" {
+                    if let Some(attribute) = bind_attribute(&class_name, condition, attribute_span, diagnostics) {
+                        attributes.push(attribute);
+                    }
+                } else if name != "class" {
+                    diagnostics.write_diagnostic(
+                        Diagnostic::error(DiagnosticCode::InvalidAttribute, attribute_span, &format!("unknown attribute prefix `{}:`", name))
+                            .help("conditional classes are written `class:name=condition`, bindings `bind:value=store.field`"));
+                } else if condition.reference_path().is_none() {
+                    diagnostics.write_diagnostic(
+                        Diagnostic::error(DiagnosticCode::InvalidAttribute, condition.span(), &format!("the condition for class `{}` must be a name", class_name))
+                            .help(&format!("e.g. `class:{}=is_selected`", class_name)));
+                } else {
+                    attributes.push(ElementAttribute::ClassToggle(class_name, condition));
+                }
+            } else {
+                try!(parser.expect(&token::Eq));
+                if name == "style" && parser.token == token::OpenDelim(token::Brace) {
+                    attributes.push(ElementAttribute::Style(try!(parse_style(ecx, parser, span))));
+                } else if name == "key" {
+                    let key = try!(parse_attribute_value(ecx, parser, span, token::Paren));
+                    if element_key(&attributes).is_some() {
+                        diagnostics.write_diagnostic(
+                            Diagnostic::error(DiagnosticCode::InvalidAttribute, attribute_span, "an element can only have one `key`"));
+                    } else if key.static_string().map_or(false, |key| key.starts_with(STATIC_KEY_PREFIX)) {
+                        diagnostics.write_diagnostic(
+                            Diagnostic::error(DiagnosticCode::InvalidAttribute, key.span(), &format!("keys starting with `{}` are reserved for static elements", STATIC_KEY_PREFIX)));
+                    } else {
+                        attributes.push(ElementAttribute::Key(key));
+                    }
+                } else {
+                    let value = try!(parse_attribute_value(ecx, parser, span, token::Paren));
+                    if AttributeKind::of(&name).is_boolean() && value.reference_path().is_none() {
+                        diagnostics.write_diagnostic(
+                            Diagnostic::error(DiagnosticCode::InvalidAttribute, value.span(), &format!("the value of boolean attribute `{}` must be a name, `true` or `false`", name))
+                                .help(&format!("e.g. `{}=is_saving`", name)));
+                    } else {
+                        attributes.push(ElementAttribute::Value(name, value));
+                    }
+                }
+            }
+
+            if !parser.eat(&token::Comma) {
+                break;
+            }
+        }
+
+        try!(parser.expect(&token::CloseDelim(token::Paren)));
+        Ok(attributes)
+    }
+
+    pub fn parse_element<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, node_type: &NodeType, diagnostics: &mut DiagnosticWrite) -> PResult<'a, Element> {
+        let element_span = parser.span;
         let element_type_token = try!(parser.parse_ident());
         let element_type = element_type_token.name.to_string().to_owned();
 
-        ecx.span_warn(span, &format!("Parsing contents ({:?}) - got element type: {:?}", node_type, &element_type));
+        debug!("Parsing contents ({:?}) - got element type: {:?}", node_type, &element_type);
+
+        let attributes = if parser.token == token::OpenDelim(token::Paren) {
+            try!(parse_attributes(ecx, parser, span, diagnostics))
+        } else {
+            vec![]
+        };
 
         try!(parser.expect(&token::OpenDelim(token::Bracket)));
 
-        let nodes = try!(parse_contents(ecx, &mut parser, span, &NodeType::Named(element_type.to_owned())));
+        let nodes = parse_contents(ecx, &mut parser, span, &NodeType::Named(element_type.to_owned()), diagnostics);
+
+        if is_void_element(&element_type) && !nodes.is_empty() {
+            diagnostics.write_diagnostic(
+                Diagnostic::error(DiagnosticCode::VoidElementContents, element_span, &format!("void element `{}` cannot have contents", element_type))
+                    .help(&format!("write `{} []`, and place the contents after it", element_type)));
+        }
 
         Ok(Element {
             element_type: element_type.to_owned(),
-            span: span,
+            span: element_span,
+            attributes: attributes,
             nodes: nodes,
         })
     }
 }
 
 pub mod output {
-    use super::Element;
+    use super::{Element, ElementAttribute, AttributeGroups, AttributeKind};
+    use syntax::ext::base::ExtCtxt;
+    use simple_expr::SimpleExpr;
+    use codegen::lang::Html;
+    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
+    use js_write::{JsWriteParamList, WriteJsSimpleExpr};
+    use codegen::naming::{bind_action_name, bind_payload_member};
     use output_actions::{OutputAction, IntoOutputActions, WriteOutputActions, OutputActionWrite};
 
+    /// Part of an opening tag in HTML
+    enum TagPart<'e> {
+        Text(String),
+        Value(&'e SimpleExpr),
+        /// Text written if the value at the path is true when rendering, such as a toggled class
+        TextIf(&'e str, String)
+    }
+
+    fn escape_attribute(value: &str) -> String {
+        value.replace('&', "&amp;").replace('"', "&quot;")
+    }
+
+    /// Values looked up when rendering are escaped as they are written, by `render::write_html_text`
+    fn value_part<'e>(simple_expr: &'e SimpleExpr) -> TagPart<'e> {
+        match simple_expr.static_string() {
+            Some(value) => TagPart::Text(escape_attribute(&value)),
+            None => TagPart::Value(simple_expr)
+        }
+    }
+
+    /// Boolean attribute, present for `true` and absent for `false`. Literal values such as `""` were
+    /// rejected when parsing, as the JS would treat them as false.
+    fn boolean_part<'e>(name: &str, condition: &'e SimpleExpr) -> Option<TagPart<'e>> {
+        match condition.reference_path() {
+            Some("false") | None => None,
+            Some("true") => Some(TagPart::Text(format!(" {}", name))),
+            Some(path) => Some(TagPart::TextIf(path, format!(" {}", name)))
+        }
+    }
+
+    fn open_tag_parts<'e>(element_type: &str, attributes: &'e [ElementAttribute], void: bool) -> Vec<TagPart<'e>> {
+        let groups = AttributeGroups::new(attributes);
+        let mut parts = vec![TagPart::Text(format!("<{}", element_type))];
+
+        // Bound values are rendered as the initial value
+        for &(name, simple_expr) in groups.values.iter().chain(groups.binds.iter()) {
+            parts.push(TagPart::Text(format!(" {}=\"", name)));
+            parts.push(value_part(simple_expr));
+            parts.push(TagPart::Text("\"".to_owned()));
+        }
+
+        // Conditions were checked to be references, `true` or `false` when parsing
+        for &(name, condition) in &groups.booleans {
+            parts.extend(boolean_part(name, condition));
+        }
+
+        if groups.class.is_some() || !groups.class_toggles.is_empty() {
+            parts.push(TagPart::Text(" class=\"".to_owned()));
+            if let Some(simple_expr) = groups.class {
+                parts.push(value_part(simple_expr));
+            }
+            for (idx, &(_, condition)) in groups.class_toggles.iter().enumerate() {
+                // Conditions were checked to be references when parsing
+                if let Some(path) = condition.reference_path() {
+                    parts.push(TagPart::TextIf(path, groups.toggled_class(idx)));
+                }
+            }
+            parts.push(TagPart::Text("\"".to_owned()));
+        }
+
+        if !groups.style.is_empty() {
+            parts.push(TagPart::Text(" style=\"".to_owned()));
+            for (idx, &(property, simple_expr)) in groups.style.iter().enumerate() {
+                let separator = if idx > 0 { " " } else { "" };
+                parts.push(TagPart::Text(format!("{}{}: ", separator, property)));
+                parts.push(value_part(simple_expr));
+                parts.push(TagPart::Text(";".to_owned()));
+            }
+            parts.push(TagPart::Text("\"".to_owned()));
+        }
+
+        parts.push(TagPart::Text((if void { " />" } else { ">" }).to_owned()));
+        parts
+    }
+
+    /// Opening tag, if all of its attributes are known at compile time
+    pub fn static_open_tag(element_type: &str, attributes: &[ElementAttribute], void: bool) -> Option<String> {
+        let mut html = String::new();
+        for part in open_tag_parts(element_type, attributes, void) {
+            match part {
+                TagPart::Text(contents) => { html.push_str(&contents); },
+                _ => { return None; }
+            }
+        }
+        Some(html)
+    }
+
+    /// Opening tag, with attribute values looked up in the state when rendering
+    pub fn write_open_tag<'cx>(ecx: &'cx ExtCtxt, w: &mut OutputStringWrite<Html>, element_type: &str, attributes: &[ElementAttribute], void: bool) {
+        for part in open_tag_parts(element_type, attributes, void) {
+            match part {
+                TagPart::Text(contents) => { w.write_output_string(ecx, &contents); },
+                TagPart::Value(simple_expr) => { simple_expr.write_output_strings(ecx, w); },
+                TagPart::TextIf(path, text) => { w.write_output_string_if(ecx, path, &text); }
+            }
+        }
+    }
+
+    /// Attribute names and values as arguments to `elementOpen`, which updates them in place when patching.
+    /// The class is built with an expression, so toggling a class changes the attribute and keeps the element.
+    /// Bound values come with an `oninput` handler dispatching the generated action to the `dispatch` of the view.
+    /// Boolean attributes are `""` or `null`, which removes them, and boolean properties are `true` or `false`.
+    pub fn write_js_attributes(attributes: &[ElementAttribute], pl: &mut JsWriteParamList) {
+        let groups = AttributeGroups::new(attributes);
+
+        for &(name, simple_expr) in &groups.values {
+            pl.param(&|ex| { ex.string_lit(name); });
+            pl.param(&|ex| { simple_expr.write_js_simple_expr(ex); });
+        }
+
+        for &(name, condition) in &groups.booleans {
+            pl.param(&|ex| { ex.string_lit(name); });
+            pl.param(&|ex| {
+                if AttributeKind::of(name) == AttributeKind::BooleanProperty {
+                    ex.conditional(&|ex| { condition.write_js_simple_expr(ex); }, &|ex| { ex.var_reference("true"); }, &|ex| { ex.var_reference("false"); });
+                } else {
+                    ex.conditional(&|ex| { condition.write_js_simple_expr(ex); }, &|ex| { ex.string_lit(""); }, &|ex| { ex.var_reference("null"); });
+                }
+            });
+        }
+
+        for &(property, path) in &groups.binds {
+            let path = match path.reference_path() {
+                Some(path) => path,
+                None => { continue; }
+            };
+            let action = bind_action_name(path);
+            let member = bind_payload_member(path);
+
+            pl.param(&|ex| { ex.string_lit(property); });
+            pl.param(&|ex| { ex.var_reference(path); });
+            pl.param(&|ex| { ex.string_lit("oninput"); });
+            pl.param(&|ex| {
+                ex.function_expr(vec!["event"], &|js| {
+                    js.call_method("dispatch", &|pl| {
+                        pl.param(&|ex| {
+                            ex.object(&|members| {
+                                members.member("type", &|ex| { ex.string_lit(&action); });
+                                members.member(&member, &|ex| { ex.var_reference(&format!("event.target.{}", property)); });
+                            });
+                        });
+                    });
+                });
+            });
+        }
+
+        if groups.class.is_some() || !groups.class_toggles.is_empty() {
+            pl.param(&|ex| { ex.string_lit("class"); });
+            pl.param(&|ex| {
+                if let Some(simple_expr) = groups.class {
+                    simple_expr.write_js_simple_expr(ex);
+                }
+                for (idx, &(_, condition)) in groups.class_toggles.iter().enumerate() {
+                    if idx > 0 || groups.class.is_some() {
+                        ex.binop_plus();
+                    }
+                    let class_name = groups.toggled_class(idx);
+                    ex.conditional(&|ex| { condition.write_js_simple_expr(ex); }, &|ex| { ex.string_lit(&class_name); }, &|ex| { ex.string_lit(""); });
+                }
+            });
+        }
+
+        if !groups.style.is_empty() {
+            pl.param(&|ex| { ex.string_lit("style"); });
+            pl.param(&|ex| {
+                ex.object(&|members| {
+                    for &(property, simple_expr) in &groups.style {
+                        let name = if property.contains('-') { format!("\"{}\"", property) } else { property.to_owned() };
+                        members.member(&name, &|ex| { simple_expr.write_js_simple_expr(ex); });
+                    }
+                });
+            });
+        }
+    }
+
     impl IntoOutputActions for Element {
         fn into_output_actions(&self) -> Vec<OutputAction> {
             let nodes = &self.nodes;
             let element_type = &self.element_type;
             let mut output_actions = Vec::new();
 
-            output_actions.push(OutputAction::WriteOpen(element_type.clone()));
+            // Contents of void elements were rejected when parsing
+            if self.is_void() {
+                output_actions.push(OutputAction::WriteVoid(element_type.clone(), self.attributes.clone(), self.span));
+                return output_actions;
+            }
+
+            output_actions.push(OutputAction::WriteOpen(element_type.clone(), self.attributes.clone(), self.span));
 
             let child_actions: Vec<OutputAction> = nodes.iter()
                 .flat_map(|node| node.into_output_actions())
                 .collect();
             output_actions.extend(child_actions);
 
-            output_actions.push(OutputAction::WriteClose(element_type.clone()));
+            output_actions.push(OutputAction::WriteClose(element_type.clone(), self.span));
 
             output_actions
         }
@@ -70,3 +591,83 @@ pub mod output {
         }
     }
 }
+
+#[test]
+fn test_attribute_kinds() {
+    assert_eq!(AttributeKind::of("title"), AttributeKind::Text);
+    assert_eq!(AttributeKind::of("disabled"), AttributeKind::Boolean);
+    assert_eq!(AttributeKind::of("value"), AttributeKind::Property);
+    assert_eq!(AttributeKind::of("checked"), AttributeKind::BooleanProperty);
+    assert!(AttributeKind::of("selected").is_boolean());
+}
+
+#[test]
+fn test_write_open_tag() {
+    use codegen::output_string_writer::OutputString;
+    use testing::{with_ecx, parse_template_source, first_element};
+
+    with_ecx(|ecx| {
+        let source = r#"store counter { default => (0) } store theme { default => ("dark") }
+            view root [ div(class=theme, class:started=counter, title="say \"hi\"", style={ font-size: "2em", color: theme }) [ ] ]"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+        assert!(diagnostics.is_empty());
+        let element = first_element(&template);
+
+        let mut output_strings: Vec<OutputString> = Vec::new();
+        output::write_open_tag(ecx, &mut output_strings, element.element_type(), element.attributes(), false);
+
+        let s = |contents: &str| OutputString::Static(contents.to_owned());
+        assert_eq!(output_strings, vec![
+            s("<div"),
+            s(" title=\""), s("say &quot;hi&quot;"), s("\""),
+            s(" class=\""), OutputString::DynamicLookup("theme".to_owned()), OutputString::StaticIf("counter".to_owned(), " started".to_owned()), s("\""),
+            s(" style=\""), s("font-size: "), s("2em"), s(";"), s(" color: "), OutputString::DynamicLookup("theme".to_owned()), s(";"), s("\""),
+            s(">")
+        ]);
+    });
+}
+
+#[test]
+fn test_void_element() {
+    use output_actions::IntoOutputActions;
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions, WriteJs};
+    use js_backend::INCREMENTAL_DOM;
+    use diagnostics::DiagnosticCode;
+    use testing::{with_ecx, parse_template_source, first_element};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ input(type="text") [ ] ]"#);
+        assert!(diagnostics.is_empty());
+        let element = first_element(&template);
+
+        let output_actions = element.into_output_actions();
+        assert_eq!(output_actions.len(), 1);
+        assert_eq!(output_actions[0].static_html(), Some("<input type=\"text\" />".to_owned()));
+
+        let mut out = String::new();
+        JsFormatter::new(&mut out, JsFormat::Minified).function("view", vec![], &|js| { element.write_js(js, &INCREMENTAL_DOM); });
+        assert_eq!(out, "function view(){IncrementalDOM.elementVoid(\"input\",null,null,\"type\",\"text\");}");
+
+        let (_, diagnostics) = parse_template_source(ecx, r#"view root [ br [ "line" ] ]"#);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), DiagnosticCode::VoidElementContents);
+    });
+}
+
+#[test]
+fn test_boolean_attributes() {
+    use output_actions::IntoOutputActions;
+    use diagnostics::DiagnosticCode;
+    use testing::{with_ecx, parse_template_source, first_element};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ button(disabled=true, hidden=false) [ ] ]"#);
+        assert!(diagnostics.is_empty());
+        assert_eq!(first_element(&template).into_output_actions()[0].static_html(), Some("<button disabled>".to_owned()));
+
+        // A literal would be present in the HTML but false in the JS
+        let (_, diagnostics) = parse_template_source(ecx, r#"view root [ button(disabled="") [ ] ]"#);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), DiagnosticCode::InvalidAttribute);
+    });
+}
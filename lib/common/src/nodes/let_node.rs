@@ -0,0 +1,100 @@
+use syntax::codemap::Span;
+use super::content_node::ContentNode;
+use simple_expr::{SimpleExpr, SimpleExprToken};
+
+
+/// `let city = data.profile.address.city [ ... ]`, naming a value for the contents of the block
+#[derive(Clone, Debug)]
+pub struct LetBlock {
+    name: String,
+    expr: SimpleExpr,
+    span: Span,
+    nodes: Vec<ContentNode>
+}
+
+impl LetBlock {
+    /// Name of the local, visible to the contents of the block
+    pub fn name(&self) -> &str { &self.name }
+    /// The value named, a reference
+    pub fn expr(&self) -> &SimpleExpr { &self.expr }
+    pub fn span(&self) -> Span { self.span }
+    pub fn nodes(&self) -> &[ContentNode] { &self.nodes }
+
+    /// Path of the value named, checked to be a reference when parsing
+    pub fn path(&self) -> &str {
+        self.expr.reference_path().unwrap_or("")
+    }
+
+    /// The name stands for its value within the block, so is a dynamic root when the value is
+    /// dynamic, and hides roots of the same name otherwise
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        self.expr.resolve_dynamic_references(roots);
+
+        let mut block_roots: Vec<String> = roots.iter()
+            .filter(|root| root.split('.').next() != Some(self.name.as_str()))
+            .cloned()
+            .collect();
+        if let Some(&SimpleExprToken::DynamicReference(_)) = self.expr.tokens().first() {
+            block_roots.push(self.name.to_owned());
+        }
+
+        for node in &mut self.nodes {
+            node.resolve_dynamic_references(&block_roots);
+        }
+    }
+}
+
+pub mod parse {
+    use super::LetBlock;
+    use syntax::codemap::{Span, mk_sp};
+    use syntax::ext::base::ExtCtxt;
+    use syntax::parse::{token, PResult};
+    use syntax::parse::parser::Parser;
+
+    use nodes::content_node::parse::{NodeType, parse_contents};
+    use simple_expr::parse::parse_simple_expr_until;
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite};
+
+    /// Parse a let block, from the `let` keyword up to and including its closing bracket
+    pub fn parse_let<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, diagnostics: &mut DiagnosticWrite) -> PResult<'a, LetBlock> {
+        let lo = parser.span.lo;
+        parser.bump();
+
+        let name = try!(parser.parse_ident()).name.to_string();
+        try!(parser.expect(&token::Eq));
+
+        let expr = try!(parse_simple_expr_until(ecx, parser, span, &|token| {
+            *token == token::OpenDelim(token::Bracket) || *token == token::Eof
+        }));
+        let let_span = mk_sp(lo, parser.prev_span.hi);
+
+        if expr.reference_path().is_none() {
+            diagnostics.write_diagnostic(
+                Diagnostic::error(DiagnosticCode::InvalidLet, expr.span(), "the value of a let must be a name")
+                    .help("e.g. `let city = data.profile.address.city [ ... ]`"));
+        }
+
+        try!(parser.expect(&token::OpenDelim(token::Bracket)));
+        let nodes = parse_contents(ecx, &mut parser, span, &NodeType::Named("let".to_owned()), diagnostics);
+        try!(parser.expect(&token::CloseDelim(token::Bracket)));
+
+        Ok(LetBlock {
+            name: name,
+            expr: expr,
+            span: let_span,
+            nodes: nodes
+        })
+    }
+}
+
+pub mod output {
+    use super::LetBlock;
+    use output_actions::{OutputAction, IntoOutputActions};
+
+    impl IntoOutputActions for LetBlock {
+        fn into_output_actions(&self) -> Vec<OutputAction> {
+            let output_actions = self.nodes.iter().flat_map(|node| node.into_output_actions()).collect();
+            vec![OutputAction::Let(self.name.to_owned(), self.path().to_owned(), output_actions, self.span)]
+        }
+    }
+}
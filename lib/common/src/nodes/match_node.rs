@@ -0,0 +1,264 @@
+use syntax::codemap::Span;
+use super::content_node::ContentNode;
+use simple_expr::{SimpleExpr, SimpleExprToken};
+
+
+/// Pattern of a match arm
+#[derive(Clone, Debug)]
+pub enum MatchPattern {
+    /// `Loaded(data)`, binding the values of the variant to names (`_` to ignore one)
+    Variant(String, Vec<String>),
+    /// `_`, matching the variants without an arm of their own
+    Wildcard
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pattern: MatchPattern,
+    span: Span,
+    nodes: Vec<ContentNode>
+}
+
+impl MatchArm {
+    pub fn pattern(&self) -> &MatchPattern { &self.pattern }
+    pub fn span(&self) -> Span { self.span }
+    pub fn nodes(&self) -> &[ContentNode] { &self.nodes }
+}
+
+/// `match load [ Loading => [ ... ], Loaded(data) => [ ... ], _ => [ ... ] ]`, rendering the
+/// contents of the arm for the variant of an enum model
+#[derive(Clone, Debug)]
+pub struct MatchBlock {
+    expr: SimpleExpr,
+    span: Span,
+    arms: Vec<MatchArm>
+}
+
+impl MatchBlock {
+    /// The value matched on, a reference to an enum
+    pub fn expr(&self) -> &SimpleExpr { &self.expr }
+    pub fn span(&self) -> Span { self.span }
+    pub fn arms(&self) -> &[MatchArm] { &self.arms }
+
+    /// Path of the value matched on, checked to be a reference when parsing
+    pub fn path(&self) -> &str {
+        self.expr.reference_path().unwrap_or("")
+    }
+
+    /// Names bound by an arm hide the roots of the same name within it, and are dynamic
+    /// themselves when the value matched on is, as for `let`
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        self.expr.resolve_dynamic_references(roots);
+        let dynamic = match self.expr.tokens().first() {
+            Some(&SimpleExprToken::DynamicReference(_)) => true,
+            _ => false
+        };
+
+        for arm in &mut self.arms {
+            let bindings: &[String] = match arm.pattern {
+                MatchPattern::Variant(_, ref bindings) => &bindings[..],
+                MatchPattern::Wildcard => &[]
+            };
+            let mut arm_roots: Vec<String> = roots.iter()
+                .filter(|root| !bindings.iter().any(|binding| root.split('.').next() == Some(binding.as_str())))
+                .cloned()
+                .collect();
+            if dynamic {
+                arm_roots.extend(bindings.iter().filter(|binding| *binding != "_").cloned());
+            }
+
+            for node in &mut arm.nodes {
+                node.resolve_dynamic_references(&arm_roots);
+            }
+        }
+    }
+}
+
+pub mod parse {
+    use super::{MatchBlock, MatchArm, MatchPattern};
+    use syntax::codemap::{Span, mk_sp};
+    use syntax::ext::base::ExtCtxt;
+    use syntax::parse::{token, PResult};
+    use syntax::parse::parser::Parser;
+
+    use nodes::content_node::parse::{NodeType, parse_contents};
+    use simple_expr::parse::parse_simple_expr_until;
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite};
+
+    /// Name bound by a pattern, or `_`
+    fn parse_binding<'a>(parser: &mut Parser<'a>) -> PResult<'a, String> {
+        if parser.eat(&token::Underscore) {
+            return Ok("_".to_owned());
+        }
+        Ok(try!(parser.parse_ident()).name.to_string())
+    }
+
+    /// `_`, `Variant` or `Variant(name, ...)`
+    fn parse_pattern<'a>(parser: &mut Parser<'a>) -> PResult<'a, MatchPattern> {
+        if parser.eat(&token::Underscore) {
+            return Ok(MatchPattern::Wildcard);
+        }
+
+        let variant_name = try!(parser.parse_ident()).name.to_string();
+        let mut bindings = Vec::new();
+        if parser.eat(&token::OpenDelim(token::Paren)) {
+            while !parser.eat(&token::CloseDelim(token::Paren)) {
+                bindings.push(try!(parse_binding(parser)));
+
+                if !parser.eat(&token::Comma) {
+                    try!(parser.expect(&token::CloseDelim(token::Paren)));
+                    break;
+                }
+            }
+        }
+
+        Ok(MatchPattern::Variant(variant_name, bindings))
+    }
+
+    /// Parse a match block, from the `match` keyword up to and including its closing bracket
+    pub fn parse_match<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, diagnostics: &mut DiagnosticWrite) -> PResult<'a, MatchBlock> {
+        let lo = parser.span.lo;
+        parser.bump();
+
+        let expr = try!(parse_simple_expr_until(ecx, parser, span, &|token| {
+            *token == token::OpenDelim(token::Bracket) || *token == token::Eof
+        }));
+        let match_span = mk_sp(lo, parser.prev_span.hi);
+
+        if expr.reference_path().is_none() {
+            diagnostics.write_diagnostic(
+                Diagnostic::error(DiagnosticCode::InvalidMatch, expr.span(), "the value matched on must be a name")
+                    .help("e.g. `match load [ Loading => [ ... ], _ => [ ... ] ]`"));
+        }
+
+        try!(parser.expect(&token::OpenDelim(token::Bracket)));
+
+        let mut arms = Vec::new();
+        while !parser.eat(&token::CloseDelim(token::Bracket)) {
+            let arm_lo = parser.span.lo;
+            let pattern = try!(parse_pattern(parser));
+            let arm_span = mk_sp(arm_lo, parser.prev_span.hi);
+            try!(parser.expect(&token::FatArrow));
+
+            try!(parser.expect(&token::OpenDelim(token::Bracket)));
+            let nodes = parse_contents(ecx, &mut parser, span, &NodeType::Named("match".to_owned()), diagnostics);
+            try!(parser.expect(&token::CloseDelim(token::Bracket)));
+
+            arms.push(MatchArm { pattern: pattern, span: arm_span, nodes: nodes });
+
+            if !parser.eat(&token::Comma) {
+                try!(parser.expect(&token::CloseDelim(token::Bracket)));
+                break;
+            }
+        }
+
+        Ok(MatchBlock {
+            expr: expr,
+            span: match_span,
+            arms: arms
+        })
+    }
+}
+
+pub mod output {
+    use super::{MatchBlock, MatchPattern};
+    use output_actions::{OutputAction, MatchArmActions, IntoOutputActions};
+
+    impl IntoOutputActions for MatchBlock {
+        fn into_output_actions(&self) -> Vec<OutputAction> {
+            let arms = self.arms.iter().map(|arm| {
+                let (variant, bindings) = match arm.pattern {
+                    MatchPattern::Variant(ref variant_name, ref bindings) => (Some(variant_name.to_owned()), bindings.clone()),
+                    MatchPattern::Wildcard => (None, vec![])
+                };
+
+                MatchArmActions {
+                    variant: variant,
+                    bindings: bindings,
+                    output_actions: arm.nodes.iter().flat_map(|node| node.into_output_actions()).collect()
+                }
+            }).collect();
+
+            vec![OutputAction::Match(self.path().to_owned(), arms, self.span)]
+        }
+    }
+}
+
+#[cfg(test)]
+fn first_match(template: &::nodes::template_node::Template) -> &MatchBlock {
+    use testing::first_view;
+
+    first_view(template).nodes().iter()
+        .filter_map(|node| match node { &ContentNode::MatchNode(ref block) => Some(block), _ => None })
+        .next().expect("view has no match")
+}
+
+#[test]
+fn test_match_dynamic_references() {
+    use testing::{with_ecx, parse_template_source};
+
+    fn arm_is_dynamic(block: &MatchBlock, arm: usize) -> bool {
+        match &block.arms()[arm].nodes()[0] {
+            &ContentNode::ExprNode(ref simple_expr) => match simple_expr.tokens().first() {
+                Some(&SimpleExprToken::DynamicReference(_)) => true,
+                _ => false
+            },
+            node => { panic!("expected an expression, got {:?}", node); }
+        }
+    }
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, "view root [ match page.load [ Loaded(data) => [ data.title ], _ => [ data.title ] ] ]");
+        assert!(diagnostics.is_empty());
+
+        // The binding of a dynamic value is dynamic
+        let mut block = first_match(&template).clone();
+        block.resolve_dynamic_references(&["page.load".to_owned()]);
+        assert!(arm_is_dynamic(&block, 0));
+        assert!(!arm_is_dynamic(&block, 1));
+
+        // The binding hides a root of the same name, within its arm only
+        let mut block = first_match(&template).clone();
+        block.resolve_dynamic_references(&["data".to_owned()]);
+        assert!(!arm_is_dynamic(&block, 0));
+        assert!(arm_is_dynamic(&block, 1));
+    });
+}
+
+#[test]
+fn test_match_output() {
+    use syntax::ast;
+    use syntax::print::pprust;
+    use codegen::lang::Html;
+    use codegen::output_stmt_writer::WriteOutputStmts;
+    use output_actions::WriteOutputActions;
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions, WriteJs};
+    use js_backend::INCREMENTAL_DOM;
+    use source_map::strip_span_markers;
+    use testing::{with_ecx, parse_template_source, first_view};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ match load [ Loaded(title) => [ p [ title ] ], _ => [ "Loading" ] ] ]"#);
+        assert!(diagnostics.is_empty());
+        let view = first_view(&template);
+
+        // Rendered in Rust with the value bound to a local
+        let mut stmts: Vec<ast::Stmt> = Vec::new();
+        WriteOutputStmts::<Html>::write_output_stmts(view, ecx, &mut stmts, ecx.ident_of("out"));
+        let rust: Vec<String> = stmts.iter().map(pprust::stmt_to_string).collect();
+        let rust = rust.join("\n");
+        assert!(rust.contains("Some(\"Loaded\") =>"));
+        assert!(rust.contains("let local_title = matched.and_then(|value| value.lookup(\"values.0\"));"));
+        assert!(rust.contains("if let Some(value) = local_title"));
+
+        // In JS, by a switch on the tag with a block scoped local for each arm
+        let mut output_actions = Vec::new();
+        view.write_output_actions(&mut output_actions);
+        let mut out = String::new();
+        JsFormatter::new(&mut out, JsFormat::Minified).function("view", vec!["load"], &|js| { output_actions.write_js(js, &INCREMENTAL_DOM); });
+        let (js, _) = strip_span_markers(&out);
+        assert_eq!(js, "function view(a){switch(a.tag){case 'Loaded':{let b=a.values[0];\
+            IncrementalDOM.elementOpen(\"p\");IncrementalDOM.text(b);IncrementalDOM.elementClose(\"p\");break;}\
+            default:{IncrementalDOM.text(\"Loading\");break;}}}");
+    });
+}
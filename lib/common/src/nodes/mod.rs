@@ -2,4 +2,7 @@ pub mod template_node;
 pub mod view_node;
 pub mod store_node;
 pub mod element_node;
-pub mod content_node;
\ No newline at end of file
+pub mod content_node;
+pub mod match_node;
+pub mod let_node;
+pub mod model_node;
@@ -0,0 +1,188 @@
+use syntax::codemap::Span;
+use types::ValueType;
+
+
+/// Represents a parsed model (struct or enum) declaration in template contents.
+/// Models describe the shape of store state, and are used to check template expressions.
+#[derive(Clone, Debug)]
+pub struct Model {
+    name: String,
+    span: Span,
+    fields: Vec<ModelField>,
+    variants: Vec<ModelVariant>
+}
+
+impl Model {
+    pub fn new(span: Span, name: &str, fields: Vec<ModelField>) -> Model {
+        Model {
+            name: name.to_owned(),
+            span: span,
+            fields: fields,
+            variants: vec![]
+        }
+    }
+
+    /// Enum model, which views can `match` on
+    pub fn new_enum(span: Span, name: &str, variants: Vec<ModelVariant>) -> Model {
+        Model { variants: variants, ..Model::new(span, name, vec![]) }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn span(&self) -> Span { self.span }
+    pub fn fields(&self) -> &[ModelField] { &self.fields }
+    pub fn variants(&self) -> &[ModelVariant] { &self.variants }
+
+    pub fn is_enum(&self) -> bool {
+        !self.variants.is_empty()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&ModelField> {
+        self.fields.iter().find(|field| field.name() == name)
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&ModelVariant> {
+        self.variants.iter().find(|variant| variant.name() == name)
+    }
+}
+
+/// Unit or tuple variant of an enum model, such as `Loading` or `Loaded(Data)`
+#[derive(Clone, Debug)]
+pub struct ModelVariant {
+    name: String,
+    span: Span,
+    values: Vec<ValueType>
+}
+
+impl ModelVariant {
+    pub fn new(span: Span, name: &str, values: Vec<ValueType>) -> ModelVariant {
+        ModelVariant {
+            name: name.to_owned(),
+            span: span,
+            values: values
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn span(&self) -> Span { self.span }
+
+    /// Types of the values of a tuple variant, empty for a unit variant
+    pub fn values(&self) -> &[ValueType] { &self.values }
+}
+
+/// A named and typed field, used for model members and action payload parameters
+#[derive(Clone, Debug)]
+pub struct ModelField {
+    name: String,
+    span: Span,
+    ty: ValueType
+}
+
+impl ModelField {
+    pub fn new(span: Span, name: &str, ty: ValueType) -> ModelField {
+        ModelField {
+            name: name.to_owned(),
+            span: span,
+            ty: ty
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn span(&self) -> Span { self.span }
+    pub fn ty(&self) -> &ValueType { &self.ty }
+}
+
+pub mod parse {
+    use super::{Model, ModelField, ModelVariant};
+    use syntax::codemap::{Span, mk_sp};
+    use syntax::ext::base::ExtCtxt;
+    use syntax::parse::{token, PResult};
+    use syntax::parse::parser::Parser;
+    use types::ValueType;
+
+    fn parse_field<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>) -> PResult<'a, ModelField> {
+        let lo = parser.span.lo;
+        let field_name = try!(parser.parse_ident()).name.to_string();
+        try!(parser.expect(&token::Colon));
+        let type_name = try!(parser.parse_ident()).name.to_string();
+        let span = mk_sp(lo, parser.prev_span.hi);
+
+        Ok(ModelField::new(span, &field_name, ValueType::from_type_name(&type_name)))
+    }
+
+    /// Parse a comma separated list of `name: Type` fields, ending with the given delimiter
+    pub fn parse_fields<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, end_delim: token::DelimToken) -> PResult<'a, Vec<ModelField>> {
+        let mut fields = Vec::new();
+
+        loop {
+            if parser.eat(&token::CloseDelim(end_delim)) {
+                break;
+            }
+
+            let field = try!(parse_field(ecx, parser));
+            fields.push(field);
+
+            if !parser.eat(&token::Comma) {
+                try!(parser.expect(&token::CloseDelim(end_delim)));
+                break;
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// `Name` or `Name(Type, ...)`
+    fn parse_variant<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>) -> PResult<'a, ModelVariant> {
+        let lo = parser.span.lo;
+        let variant_name = try!(parser.parse_ident()).name.to_string();
+
+        let mut values = Vec::new();
+        if parser.eat(&token::OpenDelim(token::Paren)) {
+            while !parser.eat(&token::CloseDelim(token::Paren)) {
+                let type_name = try!(parser.parse_ident()).name.to_string();
+                values.push(ValueType::from_type_name(&type_name));
+
+                if !parser.eat(&token::Comma) {
+                    try!(parser.expect(&token::CloseDelim(token::Paren)));
+                    break;
+                }
+            }
+        }
+        let span = mk_sp(lo, parser.prev_span.hi);
+
+        Ok(ModelVariant::new(span, &variant_name, values))
+    }
+
+    /// Comma separated list of variants, ending with the closing brace
+    fn parse_variants<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>) -> PResult<'a, Vec<ModelVariant>> {
+        let mut variants = Vec::new();
+
+        while !parser.eat(&token::CloseDelim(token::Brace)) {
+            variants.push(try!(parse_variant(ecx, parser)));
+
+            if !parser.eat(&token::Comma) {
+                try!(parser.expect(&token::CloseDelim(token::Brace)));
+                break;
+            }
+        }
+
+        Ok(variants)
+    }
+
+    /// `model Name { field: Type, ... }` for a struct, or `model Name { Variant, Variant(Type), ... }` for an enum
+    pub fn parse_model<'cx, 'a>(ecx: &'cx ExtCtxt, parser: &mut Parser<'a>, span: Span) -> PResult<'a, Model> {
+        let lo = parser.span.lo;
+        let model_name = try!(parser.parse_ident()).name.to_string();
+        let name_span = mk_sp(lo, parser.prev_span.hi);
+
+        try!(parser.expect(&token::OpenDelim(token::Brace)));
+        let is_struct = parser.token == token::CloseDelim(token::Brace) || parser.look_ahead(1, |token| token == &token::Colon);
+        if !is_struct {
+            let variants = try!(parse_variants(ecx, parser));
+            return Ok(Model::new_enum(name_span, &model_name, variants));
+        }
+
+        let fields = try!(parse_fields(ecx, parser, token::Brace));
+
+        Ok(Model::new(name_span, &model_name, fields))
+    }
+}
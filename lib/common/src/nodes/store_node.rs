@@ -1,29 +1,37 @@
 
 use syntax::codemap::Span;
 use simple_expr::SimpleExpr;
+use nodes::model_node::ModelField;
+use runtime::StoreRuntime;
 
 
 /// Represents a parsed store definition in template contents
 #[derive(Clone, Debug)]
 pub struct Store {
     name: String,
+    template_name: String,
     span: Span,
+    state_type: Option<String>,
     nodes: Vec<StoreNode>
 }
 
 impl Store {
-    pub fn with_nodes(span: Span, name: &str, nodes: Vec<StoreNode>) -> Store {
+    pub fn with_nodes(span: Span, template_name: &str, name: &str, nodes: Vec<StoreNode>) -> Store {
         Store {
             name: name.to_owned(),
+            template_name: template_name.to_owned(),
             span: span,
+            state_type: None,
             nodes: nodes
         }
     }
 
-    pub fn empty(span: Span, name: &str) -> Store {
+    pub fn empty(span: Span, template_name: &str, name: &str) -> Store {
         Store {
             name: name.to_owned(),
+            template_name: template_name.to_owned(),
             span: span,
+            state_type: None,
             nodes: vec![]
         }
     }
@@ -31,23 +39,100 @@ impl Store {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Name of the template defining the store, which scopes the generated function names
+    pub fn template_name(&self) -> &str {
+        &self.template_name
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Declared type of the store state (`store person: Person { ... }`), if any
+    pub fn state_type(&self) -> Option<&str> {
+        self.state_type.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn nodes(&self) -> &[StoreNode] {
+        &self.nodes
+    }
+
+    /// Whether the store reduces the action, either declared or generated
+    pub fn has_action(&self, act: &str) -> bool {
+        self.nodes.iter().any(|node| match node {
+            &StoreNode::ActionExpr(ref name, _, _) |
+            &StoreNode::BindAction(ref name, _) => name == act,
+            _ => false
+        })
+    }
+
+    /// Whether the store declares a reducer for the action, as opposed to one generated for a `bind:`
+    pub fn declares_action(&self, act: &str) -> bool {
+        self.nodes.iter().any(|node| match node {
+            &StoreNode::ActionExpr(ref name, _, _) => name == act,
+            _ => false
+        })
+    }
+
+    /// Generate the action setting the store (or one of its fields) for a `bind:` in a view,
+    /// unless it was generated already for another element bound to the same path
+    pub fn add_bind_action(&mut self, act: &str, field: Option<&str>) {
+        if !self.has_action(act) {
+            self.nodes.push(StoreNode::BindAction(act.to_owned(), field.map(|field| field.to_owned())));
+        }
+    }
+}
+
+/// The root store of a template, combining the state of each store under its name
+/// (as with Redux `combineReducers`).
+#[derive(Clone, Debug)]
+pub struct RootStore {
+    template_name: String,
+    store_runtime: StoreRuntime,
+    stores: Vec<Store>
+}
+
+impl RootStore {
+    pub fn new(template_name: &str, store_runtime: StoreRuntime, stores: Vec<Store>) -> RootStore {
+        RootStore {
+            template_name: template_name.to_owned(),
+            store_runtime: store_runtime,
+            stores: stores
+        }
+    }
+
+    pub fn store_runtime(&self) -> StoreRuntime {
+        self.store_runtime
+    }
+
+    pub fn stores(&self) -> &[Store] {
+        &self.stores
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum StoreNode {
     // TODO: Define nodes
     DefaultExpr(SimpleExpr),
-    ActionExpr(String, SimpleExpr)
+    /// Action reducer, with the declared payload parameters
+    ActionExpr(String, Vec<ModelField>, SimpleExpr),
+    /// Action generated for `bind:` in a view, setting the given field of the state (or the whole state)
+    /// to the payload member named after the bound path
+    BindAction(String, Option<String>)
 }
 
 pub mod parse {
     use super::{Store, StoreNode};
-    use syntax::codemap::{Span, DUMMY_SP};
+    use syntax::codemap::{Span, DUMMY_SP, mk_sp};
     use syntax::ext::base::ExtCtxt;
     use syntax::parse::{token, PResult};
     use syntax::parse::parser::Parser;
     use simple_expr::SimpleExpr;
     use simple_expr::parse::parse_simple_expr_until;
+    use nodes::model_node::parse::parse_fields;
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite, closest_name, unexpected_token};
+    use recovery::{skip_until, skip_token_tree, at_store_item};
 
     fn parse_fat_arrow_expression<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, SimpleExpr> {
         try!(parser.expect(&token::FatArrow));
@@ -61,9 +146,17 @@ pub mod parse {
 
     fn parse_action<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, StoreNode> {
         let act = try!(parser.parse_ident()).to_string().to_uppercase();
+
+        // Optional payload: action SET_NAME(name: String) => (...)
+        let params = if parser.eat(&token::OpenDelim(token::Paren)) {
+            try!(parse_fields(ecx, &mut parser, token::Paren))
+        } else {
+            vec![]
+        };
+
         let simple_expr = try!(parse_fat_arrow_expression(ecx, &mut parser, span));
 
-        Ok(StoreNode::ActionExpr(act.to_owned(), simple_expr))
+        Ok(StoreNode::ActionExpr(act.to_owned(), params, simple_expr))
     }
 
     fn parse_default<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, StoreNode> {
@@ -72,76 +165,104 @@ pub mod parse {
         Ok(StoreNode::DefaultExpr(simple_expr))
     }
 
-    fn parse_store_contents<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, Vec<StoreNode>> {
+    /// Parse store items up to and including the closing brace, resuming at the next item after an error
+    fn parse_store_contents<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, diagnostics: &mut DiagnosticWrite) -> Vec<StoreNode> {
         let mut nodes: Vec<StoreNode> = Vec::new();
 
         loop {
-            ecx.span_warn(span, &format!("Parsing store - token: {:?}", &parser.token));
+            debug!("Parsing store - token: {:?}", &parser.token);
 
             match parser.token {
                 token::CloseDelim(token::Brace) => {
-                    ecx.span_warn(span, &format!("Parsing store - complete"));
+                    debug!("Parsing store - complete");
                     parser.bump();
                     break;
                 },
 
-                token::Ident(_) => {
-                    let ident = try!(parser.parse_ident()).to_string();
-                    ecx.span_warn(DUMMY_SP, &format!("Parsing store - ident: {}", ident));
+                token::Eof | token::CloseDelim(_) => {
+                    diagnostics.write_parse_error(unexpected_token(parser, "store", "`}`"));
+                    break;
+                },
 
-                    match ident {
-                        _ if ident == "action" => {
-                            let action = try!(parse_action(ecx, parser, span));
-                            nodes.push(action);
-                            continue;
-                        },
+                token::Ident(ident) => {
+                    let ident_span = parser.span;
+                    let ident = ident.name.to_string();
+                    parser.bump();
+                    debug!("Parsing store - ident: {}", ident);
 
-                        _ if ident == "default" => {
-                            let def = try!(parse_default(ecx, parser, span));
-                            nodes.push(def);
-                            continue;
-                        },
+                    let node = match ident {
+                        _ if ident == "action" => parse_action(ecx, parser, span),
+                        _ if ident == "default" => parse_default(ecx, parser, span),
 
                         _ => {
-                            ecx.span_err(span, &format!("Parsing store - unsupported condition label: {}", &ident))
+                            let diagnostic = Diagnostic::error(DiagnosticCode::UnknownStoreLabel, ident_span, &format!("unknown store label `{}`", &ident))
+                                .note("expected `action` or `default`");
+                            let diagnostic = match closest_name(&ident, vec!["action", "default"].into_iter()) {
+                                Some(candidate) => diagnostic.suggestion(ident_span, "did you mean", candidate),
+                                None => diagnostic
+                            };
+                            Err(diagnostic.into_builder(parser.diagnostic()))
+                        }
+                    };
+
+                    match node {
+                        Ok(node) => { nodes.push(node); },
+                        Err(err) => {
+                            diagnostics.write_parse_error(err);
+                            skip_until(parser, &|parser| parser.token == token::Semi || at_store_item(parser));
+                            parser.eat(&token::Semi);
                         }
                     };
                 },
 
                 _ => {
-                    ecx.span_err(span, &format!("Parsing store - unknown token: {:?}", &parser.token));
+                    diagnostics.write_parse_error(unexpected_token(parser, "store", "`action`, `default` or `}`"));
+                    skip_token_tree(parser);
                 }
             }
-            parser.bump();
         }
 
-        Ok(nodes)
+        nodes
     }
 
-    pub fn parse_store<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, Store> {
+    pub fn parse_store<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, template_name: &str, diagnostics: &mut DiagnosticWrite) -> PResult<'a, Store> {
+        let lo = parser.span.lo;
         let store_name = try!(parser.parse_ident());
-        ecx.span_warn(DUMMY_SP, &format!("Parsing store - got name: {}", &store_name.to_string()));
+        debug!("Parsing store - got name: {}", &store_name.to_string());
+
+        let state_type = if parser.eat(&token::Colon) {
+            Some(try!(parser.parse_ident()).name.to_string())
+        } else {
+            None
+        };
+        let store_span = mk_sp(lo, parser.prev_span.hi);
 
         try!(parser.expect(&token::OpenDelim(token::Brace)));
 
-        let nodes = try!(parse_store_contents(ecx, parser, span));
+        let nodes = parse_store_contents(ecx, parser, span, diagnostics);
         for node in &nodes {
-            ecx.span_warn(DUMMY_SP, &format!("Node: {:?}", node));
+            debug!("Node: {:?}", node);
         }
 
         Ok(Store {
             name: store_name.name.to_string(),
-            span: span,
+            template_name: template_name.to_owned(),
+            span: store_span,
+            state_type: state_type,
             nodes: nodes,
         })
     }
 }
 
 pub mod output {
-    use super::{Store, StoreNode};
+    use super::{Store, StoreNode, RootStore};
     use syntax::ext::base::ExtCtxt;
     use output_actions::{OutputAction, IntoOutputActions, WriteOutputActions, OutputActionWrite};
     use js_write::{WriteJsFunctions, JsWriteFunctions, WriteJsSwitchBody, JsWriteSwitchBody, WriteJsSimpleExpr};
+    use js_backend::JsBackend;
+    use codegen::lang::Js;
+    use codegen::named_output::{NamedOutput, NamedOutputType};
+    use codegen::naming::{store_fn_name, root_store_fn_name, create_store_fn_name, bind_payload_member};
 
     impl IntoOutputActions for Store {
         fn into_output_actions(&self) -> Vec<OutputAction> {
@@ -165,35 +286,179 @@ pub mod output {
         }
     }
 
+    /// Case of a generated `bind:` action: `return Object.assign({}, person, { first_name: action.person_first_name });`,
+    /// or `return action.name;` when the whole store is bound
+    fn write_bind_case(switch: &mut JsWriteSwitchBody, store_name: &str, act: &str, field: Option<&str>) {
+        switch.case_str(act, &|js_simple| {
+            match field {
+                Some(field) => {
+                    let member = bind_payload_member(&format!("{}.{}", store_name, field));
+                    js_simple.call_params("Object.assign", &|pl| {
+                        pl.param(&|ex| { ex.object(&|_| {}); });
+                        pl.param(&|ex| { ex.var_reference(store_name); });
+                        pl.param(&|ex| {
+                            ex.object(&|members| {
+                                members.member(field, &|ex| { ex.var_reference(&format!("action.{}", member)); });
+                            });
+                        });
+                    });
+                },
+                None => { js_simple.var_reference(&format!("action.{}", bind_payload_member(store_name))); }
+            };
+        });
+    }
+
     impl WriteJsFunctions for Store {
-        fn write_js_functions(&self, funcs: &mut JsWriteFunctions) {
+        fn write_js_functions(&self, funcs: &mut JsWriteFunctions, _: &JsBackend) {
             let store_name = self.name();
-            let func_name = format!("rusttemplate_store_template_{}_{}", "main", &store_name);
+            let func_name = store_fn_name(self.template_name(), store_name);
 
             funcs.function(&func_name, vec![&store_name, "action"], &|js| {
+                for node in &self.nodes {
+                    if let &StoreNode::DefaultExpr(ref simple_expr) = node {
+                        js.if_undefined(store_name, &|js| {
+                            js.return_expr(&|js_simple| {
+                                js_simple.source_span(simple_expr.span());
+                                simple_expr.write_js_simple_expr(js_simple);
+                            });
+                        });
+                    }
+                }
+
                 js.switch_expr_simple("action.type", &|switch_body| {
                     for node in &self.nodes {
-                        node.write_js_switch_body(switch_body);
+                        match node {
+                            &StoreNode::BindAction(ref act, ref field) => {
+                                write_bind_case(switch_body, store_name, act, field.as_ref().map(|field| field.as_str()));
+                            },
+                            _ => { node.write_js_switch_body(switch_body); }
+                        };
                     }
+
+                    // Every store sees every action in the root store, so keep the slice for others
+                    switch_body.default_case(&|js_simple| {
+                        js_simple.var_reference(store_name);
+                    });
                 });
             });
         }
     }
 
+    impl WriteJsFunctions for RootStore {
+        fn write_js_functions(&self, funcs: &mut JsWriteFunctions, backend: &JsBackend) {
+            for store in &self.stores {
+                store.write_js_functions(funcs, backend);
+            }
+
+            let func_name = root_store_fn_name(&self.template_name);
+            funcs.function(&func_name, vec!["state", "action"], &|js| {
+                js.if_undefined("state", &|js| {
+                    js.assign("state", &|js_simple| {
+                        js_simple.object(&|_| {});
+                    });
+                });
+
+                js.return_expr(&|js_simple| {
+                    js_simple.object(&|members| {
+                        for store in &self.stores {
+                            let store_func_name = store_fn_name(&self.template_name, store.name());
+                            let slice = format!("state.{}", store.name());
+                            members.member(store.name(), &|js_simple| {
+                                js_simple.call(&store_func_name, &[slice.as_str(), "action"]);
+                            });
+                        }
+                    });
+                });
+            });
+
+            funcs.function(&create_store_fn_name(&self.template_name), vec!["initial_state"], &|js| {
+                js.return_expr(&|js_simple| {
+                    js_simple.call(self.store_runtime.create_store_fn(), &[func_name.as_str(), "initial_state"]);
+                });
+            });
+        }
+    }
+
+    impl NamedOutput<Js> for RootStore {
+        fn output_name(&self) -> &str { &self.template_name }
+        fn output_type(&self) -> NamedOutputType { NamedOutputType::RootStoreOutput }
+    }
+
     impl WriteJsSwitchBody for StoreNode {
         fn write_js_switch_body(&self, switch: &mut JsWriteSwitchBody) {
             match self {
-                &StoreNode::ActionExpr(ref act, ref simple_expr) => {
+                &StoreNode::ActionExpr(ref act, _, ref simple_expr) => {
                     switch.case_str(act, &|js_simple| {
+                        js_simple.source_span(simple_expr.span());
                         simple_expr.write_js_simple_expr(js_simple);
                     });
                 },
-                &StoreNode::DefaultExpr(ref simple_expr) => {
-                    switch.default_case(&|js_simple| {
-                        simple_expr.write_js_simple_expr(js_simple);
-                    });
-                }
+                // Written by the store, with its name
+                &StoreNode::BindAction(..) => {},
+                // Written as the initial state of the reducer instead
+                &StoreNode::DefaultExpr(_) => {}
             };
         }
     }
 }
+
+#[test]
+fn test_root_store() {
+    use js_write::{WriteJsFunctions, JsFormatter, JsFormat};
+    use js_backend::INCREMENTAL_DOM;
+    use runtime::StoreRuntime;
+    use testing::{with_ecx, parse_template_source};
+
+    with_ecx(|ecx| {
+        let source = r#"store counter { default => (0); action INCREMENT => (counter + 1) } store greeting { default => ("hi") }"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+        assert!(diagnostics.is_empty());
+
+        let mut out = String::new();
+        template.root_store(StoreRuntime::Redux).write_js_functions(&mut JsFormatter::new(&mut out, JsFormat::Minified), &INCREMENTAL_DOM);
+
+        // Each store reduces its own slice of the root state
+        assert!(out.contains("function rusttemplate_store_template_main(a,b){if(a === undefined){a={};}return {\
+counter:rusttemplate_store_template_main_counter(a.counter,b),greeting:rusttemplate_store_template_main_greeting(a.greeting,b),};}"));
+        assert!(out.contains("function rusttemplate_create_store_template_main(a){return Redux.createStore(rusttemplate_store_template_main,a);}"));
+    });
+}
+
+#[test]
+fn test_bind_action() {
+    use js_write::{WriteJsFunctions, JsWriteFunctions, JsFormatter, JsFormat};
+    use js_backend::{JsBackend, INCREMENTAL_DOM};
+    use runtime::StoreRuntime;
+    use testing::{with_ecx, parse_template_source, first_element};
+    use diagnostics::DiagnosticCode;
+
+    with_ecx(|ecx| {
+        let source = r#"store person { default => ("") } store account { default => ("") } store nickname { default => ("") }
+            view root [ input(bind:value=person.first_name) [ ] input(bind:value=account.first_name) [ ] input(bind:value=nickname) [ ] ]"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+        assert!(diagnostics.is_empty());
+
+        // The store bound to sets the field, or the whole store, from the action named after the whole path
+        let mut out = String::new();
+        template.root_store(StoreRuntime::Redux).write_js_functions(&mut JsFormatter::new(&mut out, JsFormat::Minified), &INCREMENTAL_DOM);
+        assert!(out.contains("case 'SET_PERSON_FIRST_NAME':return Object.assign({},a,{first_name:b.person_first_name,});"));
+        assert!(out.contains("case 'SET_ACCOUNT_FIRST_NAME':return Object.assign({},a,{first_name:b.account_first_name,});"));
+        assert!(out.contains("case 'SET_NICKNAME':return b.nickname;"));
+
+        // The input renders the value, and dispatches the action with its new value
+        let mut out = String::new();
+        JsFormatter::new(&mut out, JsFormat::Minified).function("view", vec![], &|js| {
+            INCREMENTAL_DOM.element_void(js, "input", first_element(&template).attributes());
+        });
+        assert_eq!(out, "function view(){IncrementalDOM.elementVoid(\"input\",null,null,\"value\",person.first_name,\
+            \"oninput\",function(a){dispatch({type:\"SET_PERSON_FIRST_NAME\",person_first_name:a.target.value,});});}");
+
+        // A declared action can't take the generated name
+        let source = r#"store person { default => (""); action SET_PERSON_FIRST_NAME => (person) }
+            view root [ input(bind:value=person.first_name) [ ] ]"#;
+        let (template, diagnostics) = parse_template_source(ecx, source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), DiagnosticCode::BindActionConflict);
+        assert_eq!(template.root_store(StoreRuntime::Redux).stores()[0].nodes().len(), 2);
+    });
+}
@@ -2,7 +2,9 @@
 use syntax::codemap::{Span, DUMMY_SP};
 
 use super::view_node::View;
-use super::store_node::Store;
+use super::store_node::{Store, RootStore};
+use super::model_node::Model;
+use runtime::StoreRuntime;
 
 #[derive(Clone, Debug)]
 pub struct Template {
@@ -14,23 +16,49 @@ pub struct Template {
 impl Template {
     pub fn name(&self) -> &str { &self.name }
     pub fn nodes(&self) -> &[TemplateNode] { &self.nodes }
+
+    pub fn root_store(&self, store_runtime: StoreRuntime) -> RootStore {
+        let stores = self.nodes.iter().filter_map(|node| match node {
+            &TemplateNode::StoreNode(_, ref store) => Some(store.clone()),
+            _ => None
+        }).collect();
+        RootStore::new(&self.name, store_runtime, stores)
+    }
 }
 
 // Represents a parsed node in template contents
 #[derive(Clone, Debug)]
 pub enum TemplateNode {
     ViewNode(String, View),
-    StoreNode(String, Store)
+    StoreNode(String, Store),
+    ModelNode(String, Model)
     // TODO: RootNode
 }
 
+impl TemplateNode {
+    /// Returns true if this node generates render output (models only carry type information)
+    pub fn has_output(&self) -> bool {
+        match self {
+            &TemplateNode::ModelNode(_, _) => false,
+            _ => true
+        }
+    }
+}
+
 pub mod output {
     use super::{Template, TemplateNode};
     use syntax::ext::base::ExtCtxt;
+    use nodes::store_node::StoreNode;
     use output_actions::{OutputAction, WriteOutputActions, OutputActionWrite};
     use js_write::{WriteJsFunctions, JsWriteFunctions};
-    use codegen::lang::{Lang, Html, Js};
+    use js_backend::JsBackend;
+    use ts_write::{WriteTsDeclarations, TsWrite, ts_type, ts_object_type, ts_type_prefix};
+    use typecheck::TypeChecker;
+    use model::{registry, ENUM_TAG, ENUM_VALUES};
+    use types::ValueType;
+    use codegen::lang::{Lang, Html, Js, Ts};
     use codegen::named_output::{NamedOutput, NamedOutputType, WriteNamedOutputs, NamedOutputWrite};
+    use codegen::naming::{store_fn_name, root_store_fn_name, create_store_fn_name, patch_fn_name, view_calls_fn_name};
     use codegen::output_string_writer::WriteOutputStrings;
 
     /*
@@ -48,18 +76,25 @@ pub mod output {
     }
     */
 
+    impl NamedOutput<Ts> for Template {
+        fn output_name(&self) -> &str { &self.name }
+        fn output_type(&self) -> NamedOutputType { NamedOutputType::TypesOutput }
+    }
+
     impl<L: Lang> NamedOutput<L> for TemplateNode {
         fn output_name(&self) -> &str {
             match self {
                 &TemplateNode::ViewNode(ref view_name, _) => view_name,
-                &TemplateNode::StoreNode(ref store_name, _) => store_name
+                &TemplateNode::StoreNode(ref store_name, _) => store_name,
+                &TemplateNode::ModelNode(ref model_name, _) => model_name
             }
         }
 
         fn output_type(&self) -> NamedOutputType {
             match self {
                 &TemplateNode::ViewNode(_, _) => NamedOutputType::ViewOutput,
-                &TemplateNode::StoreNode(_, _) => NamedOutputType::StoreOutput
+                &TemplateNode::StoreNode(_, _) => NamedOutputType::StoreOutput,
+                &TemplateNode::ModelNode(_, _) => NamedOutputType::ModelOutput
             }
         }
     }
@@ -69,6 +104,7 @@ pub mod output {
             match self {
                 &TemplateNode::ViewNode(ref view_name, ref view) => view.write_output_actions(w),
                 &TemplateNode::StoreNode(ref store_name, ref store) => store.write_output_actions(w),
+                &TemplateNode::ModelNode(_, _) => {}
             }
         }
     }
@@ -82,18 +118,136 @@ pub mod output {
     }
 
     impl WriteJsFunctions for Template {
-        fn write_js_functions(&self, w: &mut JsWriteFunctions) {
+        fn write_js_functions(&self, w: &mut JsWriteFunctions, backend: &JsBackend) {
+            for node in &self.nodes {
+                node.write_js_functions(w, backend);
+            }
+        }
+    }
+
+    impl WriteTsDeclarations for Template {
+        fn write_ts_declarations<'cx>(&self, ecx: &'cx ExtCtxt, ts: &mut TsWrite) {
+            // Resolve declared and inferred types, errors were already reported when expanding
+            let mut checker = TypeChecker::quiet(ecx);
+            checker.check_template(self);
+
+            let prefix = ts_type_prefix(&self.name);
+            let state_name = format!("{}State", &prefix);
+            let action_name = format!("{}Action", &prefix);
+            ts.comment(&format!("Declarations for template {}", &self.name));
+
+            let declared_models = self.nodes.iter().filter_map(|node| match node {
+                &TemplateNode::ModelNode(_, ref model) => Some(model.clone()),
+                _ => None
+            });
+            let derived_models = registry::registered_models().into_iter()
+                .filter(|model| !self.nodes.iter().any(|node| match node {
+                    &TemplateNode::ModelNode(ref model_name, _) => model_name == model.name(),
+                    _ => false
+                }));
+
+            for model in derived_models.chain(declared_models) {
+                if model.is_enum() {
+                    // Serialized with the variant name and its values, e.g. `{ tag: "Loaded"; values: [Data] }`
+                    let variants: Vec<String> = model.variants().iter()
+                        .map(|variant| {
+                            let values: Vec<String> = variant.values().iter().map(|ty| ts_type(ty)).collect();
+                            ts_object_type(&vec![
+                                (ENUM_TAG.to_owned(), format!("\"{}\"", variant.name())),
+                                (ENUM_VALUES.to_owned(), format!("[{}]", values.join(", ")))
+                            ])
+                        })
+                        .collect();
+                    ts.type_alias(model.name(), &variants.join(" | "));
+                    continue;
+                }
+
+                let members: Vec<(String, String)> = model.fields().iter()
+                    .map(|field| (field.name().to_owned(), ts_type(field.ty())))
+                    .collect();
+                ts.interface(model.name(), &members);
+            }
+
+            let mut state_members = Vec::new();
+            let mut store_action_names = Vec::new();
             for node in &self.nodes {
-                node.write_js_functions(w);
+                if let &TemplateNode::StoreNode(ref store_name, ref store) = node {
+                    let state_ty = ts_type(checker.store_type(store_name).unwrap_or(&ValueType::Unknown));
+                    let store_action_name = format!("{}{}Action", &prefix, ts_type_prefix(store_name));
+
+                    let actions: Vec<String> = store.nodes().iter()
+                        .filter_map(|node| match node {
+                            &StoreNode::ActionExpr(ref act, ref params, _) => {
+                                let mut members = vec![("type".to_owned(), format!("\"{}\"", act))];
+                                members.extend(params.iter().map(|param| (param.name().to_owned(), ts_type(param.ty()))));
+                                Some(ts_object_type(&members))
+                            },
+                            // Bound inputs dispatch their text
+                            &StoreNode::BindAction(ref act, ref field) => {
+                                let member = field.as_ref().map(|field| field.as_str()).unwrap_or(store_name);
+                                Some(ts_object_type(&vec![("type".to_owned(), format!("\"{}\"", act)), (member.to_owned(), "string".to_owned())]))
+                            },
+                            _ => None
+                        })
+                        .collect();
+
+                    let actions_ty = if actions.is_empty() { "never".to_owned() } else { actions.join(" | ") };
+                    ts.type_alias(&store_action_name, &actions_ty);
+
+                    let func_name = store_fn_name(&self.name, store_name);
+                    // Stores see every action dispatched to the root store
+                    let params = vec![(store_name.to_owned(), format!("{} | undefined", &state_ty)), ("action".to_owned(), action_name.to_owned())];
+                    ts.declare_function(&func_name, &params, &state_ty);
+
+                    state_members.push((store_name.to_owned(), state_ty));
+                    store_action_names.push(store_action_name);
+                }
             }
+
+            // The root state holds each store's state under the store name
+            ts.interface(&state_name, &state_members);
+
+            let actions_ty = if store_action_names.is_empty() { "never".to_owned() } else { store_action_names.join(" | ") };
+            ts.type_alias(&action_name, &actions_ty);
+
+            let root_params = vec![("state".to_owned(), format!("{} | undefined", &state_name)), ("action".to_owned(), action_name.to_owned())];
+            ts.declare_function(&root_store_fn_name(&self.name), &root_params, &state_name);
+
+            let store_type_name = format!("{}Store", &prefix);
+            ts.interface(&store_type_name, &vec![
+                ("dispatch".to_owned(), format!("(action: {}) => void", &action_name)),
+                ("getState".to_owned(), format!("() => {}", &state_name)),
+                ("subscribe".to_owned(), "(listener: () => void) => () => void".to_owned())
+            ]);
+
+            let create_params = vec![("initial_state".to_owned(), format!("{} | undefined", &state_name))];
+            ts.declare_function(&create_store_fn_name(&self.name), &create_params, &store_type_name);
+
+            let dispatch_ty = format!("(action: {}) => void", &action_name);
+            for node in &self.nodes {
+                if let &TemplateNode::ViewNode(_, ref view) = node {
+                    let func_name = view_calls_fn_name(&self.name, view.name());
+                    let view_params = vec![("state".to_owned(), state_name.to_owned()), ("dispatch".to_owned(), dispatch_ty.to_owned())];
+                    ts.declare_function(&func_name, &view_params, "void");
+                }
+            }
+
+            let patch_params = vec![
+                ("root".to_owned(), "Element".to_owned()),
+                ("view".to_owned(), format!("(state: {}, dispatch: {}) => void", &state_name, &dispatch_ty)),
+                ("state".to_owned(), state_name.to_owned()),
+                ("dispatch".to_owned(), dispatch_ty.to_owned())
+            ];
+            ts.declare_function(&patch_fn_name(&self.name), &patch_params, "void");
         }
     }
 
     impl WriteJsFunctions for TemplateNode {
-        fn write_js_functions(&self, w: &mut JsWriteFunctions) {
+        fn write_js_functions(&self, w: &mut JsWriteFunctions, backend: &JsBackend) {
             match self {
-                &TemplateNode::ViewNode(ref view_name, ref view) => { view.write_js_functions(w); },
-                &TemplateNode::StoreNode(ref store_name, ref store) => { store.write_js_functions(w); },
+                &TemplateNode::ViewNode(ref view_name, ref view) => { view.write_js_functions(w, backend); },
+                &TemplateNode::StoreNode(ref store_name, ref store) => { store.write_js_functions(w, backend); },
+                &TemplateNode::ModelNode(_, _) => {}
             }
         }
     }
@@ -140,78 +294,184 @@ pub mod parse {
     use nodes::view_node::parse::parse_view;
     use nodes::store_node::Store;
     use nodes::store_node::parse::parse_store;
+    use nodes::model_node::parse::parse_model;
+    use types::ValueType;
     use simple_expr::SimpleExpr;
     use simple_expr::parse::parse_simple_expr;
-
-    pub fn parse_template<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, name: &str) -> PResult<'a, Template> {
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite, closest_name, unexpected_token};
+    use recovery::at_template_item;
+    use codegen::naming::bind_action_name;
+    use model::registry;
+
+    /// Parse the template contents, returning the parsed items along with any errors found.
+    /// After an error, parsing resumes at the next `view`, `store` or `model` item.
+    pub fn parse_template<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, name: &str) -> (Template, Vec<Diagnostic>) {
         let mut nodes = Vec::new();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
 
         loop {
-            ecx.span_warn(span, &format!("Parsing template - got token: {:?}", &parser.token));
+            debug!("Parsing template - got token: {:?}", &parser.token);
 
             match parser.token {
-                token::CloseDelim(token::Bracket) => {
-                    ecx.span_warn(span, "Parsing template - got closing bracket");
+                token::Eof => {
+                    debug!("Parsing template - complete");
                     break;
                 },
 
-                token::Ident(_) => {
-                    let keyword_token = try!(parser.parse_ident());
+                token::Ident(keyword_token) => {
+                    let keyword_span = parser.span;
                     let keyword = keyword_token.name.to_string().to_owned();
+                    parser.bump();
 
-                    ecx.span_warn(span, &format!("Parsing template - got keyword: {:?}", &keyword));
-                    match keyword.as_ref() {
+                    debug!("Parsing template - got keyword: {:?}", &keyword);
+                    let node = match keyword.as_ref() {
                         "view" => {
-                            ecx.span_warn(span, "Parsing view");
-                            let view = try!(parse_view(ecx, &mut parser, span));
-                            nodes.push(TemplateNode::ViewNode("root".to_owned(), view));
+                            debug!("Parsing view");
+                            parse_view(ecx, &mut parser, span, name, &mut diagnostics)
+                                .map(|view| TemplateNode::ViewNode(view.name().to_owned(), view))
                         },
 
                         "store" => {
-                            ecx.span_warn(span, "Parsing store");
-                            let store = try!(parse_store(ecx, &mut parser, span));
-                            let store_name = store.name().to_owned();
+                            debug!("Parsing store");
+                            parse_store(ecx, &mut parser, span, name, &mut diagnostics)
+                                .map(|store| TemplateNode::StoreNode(store.name().to_owned(), store))
+                        },
 
-                            nodes.push(TemplateNode::StoreNode(store_name, store));
+                        "model" => {
+                            debug!("Parsing model");
+                            parse_model(ecx, &mut parser, span)
+                                .map(|model| TemplateNode::ModelNode(model.name().to_owned(), model))
                         }
 
                         _ => {
-                            ecx.span_warn(span, &format!("Parsing template - got other keyword: {:?}", keyword));
+                            let keywords = vec!["view", "store", "model"];
+                            let diagnostic = Diagnostic::error(DiagnosticCode::UnknownTemplateKeyword, keyword_span, &format!("unknown template item `{}`", &keyword))
+                                .note("expected `view`, `store` or `model`");
+                            let diagnostic = match closest_name(&keyword, keywords.into_iter()) {
+                                Some(candidate) => diagnostic.suggestion(keyword_span, "did you mean", candidate),
+                                None => diagnostic
+                            };
+                            Err(diagnostic.into_builder(parser.diagnostic()))
                         }
-                    }
-
-                    /*
-                    let keyword = if let token::Ident(ref ident) = parser.token {
-                        let keyword = ident.name.to_string().to_owned();
-                        ecx.span_warn(span, &format!("Parsing template contents - got keyword: {:?}", &keyword));
-                        keyword
                     };
-                    */
 
-                    /*
-                    let keyword = parser.token.name.to_string().to_owned();
+                    match node {
+                        Ok(node) => { nodes.push(node); },
+                        Err(err) => {
+                            diagnostics.write_parse_error(err);
+                            skip_to_template_item(parser);
+                        }
+                    };
+                },
 
-                    ecx.span_warn(span, &format!("Parsing expression - got keyword: {:?}", &keyword));
-                    match keyword.as_ref() {
-                        "view" => {
-                            ecx.span_warn(span, "Parsing view");
-                        },
+                _ => {
+                    diagnostics.write_parse_error(unexpected_token(parser, "template", "`view`, `store` or `model`"));
+                    parser.bump();
+                    skip_to_template_item(parser);
+                }
+            }
+        }
 
-                        _ => {
-                            ecx.span_warn(span, &format!("Parsing element: {:?}", keyword));
+        // Member access on stores (or model fields) declared `Any` is unchecked, and rendered dynamically
+        let mut dynamic_roots: Vec<String> = Vec::new();
+        for node in &nodes {
+            if let &TemplateNode::StoreNode(ref store_name, ref store) = node {
+                match store.state_type() {
+                    Some("Any") => { dynamic_roots.push(store_name.to_owned()); },
+                    Some(type_name) => {
+                        // Looked up as the type checker does: declared in the template, or derived before it
+                        let model = nodes.iter().filter_map(|node| match node {
+                            &TemplateNode::ModelNode(ref model_name, ref model) if model_name == type_name => Some(model.clone()),
+                            _ => None
+                        }).next().or_else(|| registry::lookup_model(type_name));
+
+                        if let Some(model) = model {
+                            for field in model.fields().iter().filter(|field| field.ty() == &ValueType::Any) {
+                                dynamic_roots.push(format!("{}.{}", store_name, field.name()));
+                            }
                         }
+                    },
+                    None => {}
+                }
+            }
+        }
+
+        let store_names: Vec<String> = nodes.iter().filter_map(|node| match node {
+            &TemplateNode::StoreNode(ref store_name, _) => Some(store_name.to_owned()),
+            _ => None
+        }).collect();
+
+        for node in &mut nodes {
+            if let &mut TemplateNode::ViewNode(_, ref mut view) = node {
+                view.bind_stores(&store_names);
+                view.resolve_dynamic_references(&dynamic_roots);
+            }
+        }
+
+        // Stores reduce the actions generated for inputs bound to them
+        let bound_paths: Vec<(String, Span)> = nodes.iter().flat_map(|node| match node {
+            &TemplateNode::ViewNode(_, ref view) => view.bound_paths(),
+            _ => vec![]
+        }).collect();
+
+        for &(ref path, path_span) in &bound_paths {
+            let mut segments = path.splitn(2, '.');
+            let (root, field) = (segments.next().unwrap_or(""), segments.next());
+            let act = bind_action_name(path);
+            for node in &mut nodes {
+                if let &mut TemplateNode::StoreNode(ref store_name, ref mut store) = node {
+                    if store_name != root {
+                        continue;
                     }
-                    */
-                },
 
-                _ => {
-                    ecx.span_err(span, &format!("Parsing template - got unexpected token: {:?}", parser.token));
+                    // The declared reducer would get the generated dispatches, with a payload it doesn't expect
+                    if store.declares_action(&act) {
+                        diagnostics.write_diagnostic(
+                            Diagnostic::error(DiagnosticCode::BindActionConflict, path_span, &format!("binding `{}` generates the action `{}`, which store `{}` already declares", path, act, store_name))
+                                .span_note(store.span(), &format!("`{}` is declared in this store", act))
+                                .help(&format!("rename the declared action, as `{}` is reserved for the binding", act)));
+                    } else {
+                        store.add_bind_action(&act, field);
+                    }
                 }
             }
         }
 
         let template = Template { name: name.to_owned(), span: span, nodes: nodes };
-        Ok(template)
+        (template, diagnostics)
     }
 
+    /// Skip to the next template item, regardless of nesting, as the error may be inside an element
+    fn skip_to_template_item(parser: &mut Parser) {
+        while parser.token != token::Eof && !at_template_item(parser) {
+            parser.bump();
+        }
+    }
+
+}
+
+#[test]
+fn test_dynamic_roots_of_derived_models() {
+    use syntax::codemap::DUMMY_SP;
+    use nodes::content_node::ContentNode;
+    use nodes::model_node::{Model, ModelField};
+    use simple_expr::SimpleExprToken;
+    use types::ValueType;
+    use model::registry;
+    use testing::{with_ecx, parse_template_source, first_view};
+
+    registry::register_model(Model::new(DUMMY_SP, "Page", vec![ModelField::new(DUMMY_SP, "data", ValueType::Any)]));
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, "store page: Page { } view root [ page.data.title ]");
+        assert!(diagnostics.is_empty());
+
+        match &first_view(&template).nodes()[0] {
+            &ContentNode::ExprNode(ref simple_expr) => match simple_expr.tokens()[0] {
+                SimpleExprToken::DynamicReference(ref path) => { assert_eq!(path, "page.data.title"); },
+                ref token => { panic!("expected a dynamic reference, got {:?}", token); }
+            },
+            node => { panic!("expected an expression, got {:?}", node); }
+        };
+    });
 }
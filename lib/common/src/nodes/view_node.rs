@@ -11,12 +11,15 @@ use simple_expr::SimpleExpr;
 use simple_expr::parse::parse_simple_expr;
 
 use nodes::content_node::ContentNode;
+use nodes::element_node::ElementAttribute;
 
 /// Represents a parsed view in template contents
 #[derive(Clone, Debug)]
 pub struct View {
     name: String,
+    template_name: String,
     span: Span,
+    stores: Vec<String>,
     nodes: Vec<ContentNode>
 }
 
@@ -24,28 +27,95 @@ impl View {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Name of the template defining the view, which scopes the generated function names
+    pub fn template_name(&self) -> &str {
+        &self.template_name
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn nodes(&self) -> &[ContentNode] {
+        &self.nodes
+    }
+
+    /// Names of the store slices the view reads from the root state
+    pub fn stores(&self) -> &[String] {
+        &self.stores
+    }
+
+    /// Paths bound with `bind:` on elements of the view, such as `person.first_name`, with the span of each
+    pub fn bound_paths(&self) -> Vec<(String, Span)> {
+        let mut paths = Vec::new();
+        collect_bound_paths(&self.nodes, &mut paths);
+        paths
+    }
+
+    pub fn bind_stores(&mut self, store_names: &[String]) {
+        self.stores = store_names.to_vec();
+    }
+
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        for node in &mut self.nodes {
+            node.resolve_dynamic_references(roots);
+        }
+    }
+}
+
+fn collect_bound_paths(nodes: &[ContentNode], paths: &mut Vec<(String, Span)>) {
+    for node in nodes {
+        match node {
+            &ContentNode::ElementNode(ref element) => {
+                for attribute in element.attributes() {
+                    if let &ElementAttribute::Bind(_, ref simple_expr) = attribute {
+                        paths.extend(simple_expr.reference_path().map(|path| (path.to_owned(), simple_expr.span())));
+                    }
+                }
+                collect_bound_paths(element.nodes(), paths);
+            },
+
+            &ContentNode::MatchNode(ref block) => {
+                for arm in block.arms() {
+                    collect_bound_paths(arm.nodes(), paths);
+                }
+            },
+
+            &ContentNode::LetNode(ref block) => {
+                collect_bound_paths(block.nodes(), paths);
+            },
+
+            _ => {}
+        }
+    }
 }
 
 pub mod parse {
     use super::View;
     use syntax::tokenstream::TokenTree;
-    use syntax::codemap::{Span, DUMMY_SP};
+    use syntax::codemap::{Span, DUMMY_SP, mk_sp};
     use syntax::ext::base::ExtCtxt;
     use syntax::parse::{token, PResult};
     use syntax::parse::parser::Parser;
     use nodes::content_node::parse::{NodeType, parse_contents};
+    use diagnostics::DiagnosticWrite;
 
-    pub fn parse_view<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, View> {
-        //let view_token = try!(parser.parse_ident());
+    pub fn parse_view<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, template_name: &str, diagnostics: &mut DiagnosticWrite) -> PResult<'a, View> {
+        let lo = parser.span.lo;
         let view_name = try!(parser.parse_ident());
+        let view_span = mk_sp(lo, parser.prev_span.hi);
 
         try!(parser.expect(&token::OpenDelim(token::Bracket)));
 
-        let nodes = try!(parse_contents(ecx, parser, span, &NodeType::Root));
+        let nodes = parse_contents(ecx, parser, span, &NodeType::Root, diagnostics);
+        try!(parser.expect(&token::CloseDelim(token::Bracket)));
 
         Ok(View {
             name: view_name.name.to_string(),
-            span: span,
+            template_name: template_name.to_owned(),
+            span: view_span,
+            stores: vec![],
             nodes: nodes,
         })
     }
@@ -56,6 +126,8 @@ mod output {
     use syntax::ext::base::ExtCtxt;
     use output_actions::{OutputAction, IntoOutputActions, WriteOutputActions, OutputActionWrite};
     use js_write::{WriteJsFunctions, JsWriteFunctions, WriteJs};
+    use codegen::naming::view_calls_fn_name;
+    use js_backend::JsBackend;
 
     impl IntoOutputActions for View {
         fn into_output_actions<'cx>(&self) -> Vec<OutputAction> {
@@ -79,16 +151,21 @@ mod output {
     }
 
     impl WriteJsFunctions for View {
-        fn write_js_functions(&self, funcs: &mut JsWriteFunctions) {
-            let view_name = self.name();
-            let func_name = format!("rusttemplate_render_template_{}_view_{}_calls", "main", &view_name);
+        fn write_js_functions(&self, funcs: &mut JsWriteFunctions, backend: &JsBackend) {
+            let func_name = view_calls_fn_name(self.template_name(), self.name());
 
             let mut output_actions = Vec::new();
             self.write_output_actions(&mut output_actions);
 
-            // TODO: Generate the argument list
-            funcs.function(&func_name, vec!["counter"], &|js| {
-                output_actions.write_js(js);
+            // The view is passed the root state, each store is read from its slice,
+            // and the store's dispatch for the actions of bound inputs
+            funcs.function(&func_name, vec!["state", "dispatch"], &|js| {
+                for store_name in &self.stores {
+                    js.let_statement(store_name, &|js_simple| {
+                        js_simple.var_reference(&format!("state.{}", store_name));
+                    });
+                }
+                output_actions.write_js(js, backend);
             });
         }
     }
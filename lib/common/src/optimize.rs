@@ -0,0 +1,79 @@
+use output_actions::OutputAction;
+
+
+/// Merge each run of static output actions into a single `WriteHtml`, for rendering HTML.
+/// Element tags are plain text in HTML, so a subtree without expressions becomes one
+/// string computed at compile time, and is written with a single call when rendering.
+/// The JS output keeps the element actions, which the rendering backends need.
+pub fn coalesce_static_html(output_actions: &[OutputAction]) -> Vec<OutputAction> {
+    let mut coalesced = Vec::with_capacity(output_actions.len());
+    let mut contents = String::new();
+
+    for output_action in output_actions {
+        match output_action.static_html() {
+            Some(html) => { contents.push_str(&html); },
+            None => {
+                if !contents.is_empty() {
+                    coalesced.push(OutputAction::WriteHtml(contents));
+                    contents = String::new();
+                }
+                coalesced.push(output_action.clone());
+            }
+        }
+    }
+
+    if !contents.is_empty() {
+        coalesced.push(OutputAction::WriteHtml(contents));
+    }
+
+    debug!("Coalesced {} output actions into {}", output_actions.len(), coalesced.len());
+    coalesced
+}
+
+/// Index of the `WriteClose` of the element opened at `open`, if its contents are static
+/// (there are no expressions or dynamic attributes in the subtree) and not empty
+pub fn static_element_end(output_actions: &[OutputAction], open: usize) -> Option<usize> {
+    if output_actions[open].static_html().is_none() {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (idx, output_action) in output_actions.iter().enumerate().skip(open + 1) {
+        match output_action {
+            &OutputAction::WriteOpen(..) |
+            &OutputAction::WriteVoid(..) if output_action.static_html().is_none() => { return None; },
+            &OutputAction::WriteOpen(..) => { depth += 1; },
+            &OutputAction::WriteClose(..) if depth == 0 => {
+                return if idx > open + 1 { Some(idx) } else { None };
+            },
+            &OutputAction::WriteClose(..) => { depth -= 1; },
+            &OutputAction::WriteResult(_) |
+            &OutputAction::Match(..) |
+            &OutputAction::Let(..) => { return None; },
+            _ => {}
+        }
+    }
+    None
+}
+
+#[test]
+fn test_coalesce_static_html() {
+    use syntax::codemap::DUMMY_SP;
+
+    let output_actions = vec![
+        OutputAction::WriteOpen("div".to_owned(), vec![], DUMMY_SP),
+        OutputAction::WriteOpen("h1".to_owned(), vec![], DUMMY_SP),
+        OutputAction::Write("Counter & <total>: ".to_owned()),
+        OutputAction::WriteClose("h1".to_owned(), DUMMY_SP),
+        OutputAction::Flush,
+        OutputAction::WriteClose("div".to_owned(), DUMMY_SP)
+    ];
+
+    let coalesced: Vec<Option<String>> = coalesce_static_html(&output_actions).iter()
+        .map(|output_action| output_action.static_html())
+        .collect();
+    assert_eq!(coalesced, vec![Some("<div><h1>Counter &amp; &lt;total&gt;: </h1>".to_owned()), None, Some("</div>".to_owned())]);
+
+    assert_eq!(static_element_end(&output_actions, 0), Some(5));
+    assert_eq!(static_element_end(&output_actions, 1), Some(3));
+}
@@ -1,5 +1,6 @@
 
 use syntax::ast;
+use syntax::codemap::Span;
 use syntax::parse::token;
 use syntax::ext::base::{DummyResult, ExtCtxt, MacEager, MacResult};
 use syntax::ext::quote::rt::ToTokens;
@@ -10,8 +11,18 @@ use codegen::IntoWriteStmt;
 
 use simple_expr::{SimpleExpr, js_write};
 use js_write::{WriteJs, JsWrite, WriteJsSimpleExpr};
+use model::{ENUM_TAG, ENUM_VALUES};
+use js_backend::JsBackend;
+use optimize::static_element_end;
+use nodes::element_node::ElementAttribute;
+use nodes::element_node::output::static_open_tag;
+use render::escape_html;
 
 
+/// Prefix of the keys generated for static elements in the JS. It is reserved: a `key=` starting
+/// with it could be matched to a static element when patching, whose contents are skipped.
+pub const STATIC_KEY_PREFIX: &'static str = "incrust-static";
+
 pub trait WriteOutputActions {
     fn write_output_actions(&self, w: &mut OutputActionWrite);
 }
@@ -31,10 +42,63 @@ pub enum OutputAction {
     Write(String),
     WriteResult(SimpleExpr),
 
-    // Elements
-    WriteOpen(String),
-    WriteClose(String),
-    WriteVoid(String),
+    /// Markup known at compile time, merged from static actions when rendering HTML
+    WriteHtml(String),
+
+    // Elements, with the span of the element in the template
+    WriteOpen(String, Vec<ElementAttribute>, Span),
+    WriteClose(String, Span),
+    WriteVoid(String, Vec<ElementAttribute>, Span),
+
+    /// Send the output rendered so far, when streaming
+    Flush,
+
+    /// Render the arm matching the variant of the enum at the path, with the span of the match block
+    Match(String, Vec<MatchArmActions>, Span),
+
+    /// Render the actions with the name bound to the value at the path, with the span of the let block
+    Let(String, String, Vec<OutputAction>, Span),
+}
+
+/// Arm of an `OutputAction::Match`: the variant it matches (`None` for `_`),
+/// the names bound to the values of the variant, and the actions rendering its contents
+#[derive(Clone, Debug)]
+pub struct MatchArmActions {
+    pub variant: Option<String>,
+    pub bindings: Vec<String>,
+    pub output_actions: Vec<OutputAction>
+}
+
+impl OutputAction {
+    /// Template source the action was generated from, if known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            &OutputAction::Write(_) |
+            &OutputAction::WriteHtml(_) |
+            &OutputAction::Flush => None,
+            &OutputAction::WriteResult(ref simple_expr) => Some(simple_expr.span()),
+            &OutputAction::WriteOpen(_, _, span) |
+            &OutputAction::WriteClose(_, span) |
+            &OutputAction::WriteVoid(_, _, span) |
+            &OutputAction::Match(_, _, span) |
+            &OutputAction::Let(_, _, _, span) => Some(span)
+        }
+    }
+
+    /// HTML written by the action, if it is known at compile time
+    pub fn static_html(&self) -> Option<String> {
+        match self {
+            &OutputAction::Write(ref contents) => Some(escape_html(contents)),
+            &OutputAction::WriteHtml(ref html) => Some(html.to_owned()),
+            &OutputAction::WriteOpen(ref element_type, ref attributes, _) => static_open_tag(element_type, attributes, false),
+            &OutputAction::WriteClose(ref element_type, _) => Some(format!("</{}>", element_type)),
+            &OutputAction::WriteVoid(ref element_type, ref attributes, _) => static_open_tag(element_type, attributes, true),
+            &OutputAction::WriteResult(_) |
+            &OutputAction::Flush |
+            &OutputAction::Match(..) |
+            &OutputAction::Let(..) => None
+        }
+    }
 }
 
 mod output_strings {
@@ -42,13 +106,16 @@ mod output_strings {
     use syntax::codemap::{DUMMY_SP, Span};
     use syntax::ext::base::ExtCtxt;
     use codegen::lang::{Lang, Js, Html};
-    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
+    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite, OutputString, MatchArmStrings};
+    use optimize::coalesce_static_html;
+    use nodes::element_node::output::write_open_tag;
+    use render::escape_html;
 
     impl<S: WriteOutputActions> WriteOutputStrings<Html> for S {
         fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Html>) {
             let mut output_actions = Vec::new();
             self.write_output_actions(&mut output_actions);
-            for output_action in &output_actions {
+            for output_action in &coalesce_static_html(&output_actions) {
                 output_action.write_output_strings(ecx, w);
             }
         }
@@ -56,28 +123,68 @@ mod output_strings {
 
     impl WriteOutputStrings<Html> for OutputAction {
         fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Html>) {
-            ecx.span_warn(DUMMY_SP, &format!("Writing output action: {:?}", &self));
+            debug!("Writing output action: {:?}", &self);
             match self {
                 &OutputAction::Write(ref contents) => {
-                    ecx.span_warn(DUMMY_SP, &format!("Writing output string for Write output action: {}", contents));
-                    w.write_output_string(ecx, &contents);
+                    debug!("Writing output string for Write output action: {}", contents);
+                    w.write_output_string(ecx, &escape_html(contents));
+                },
+
+                &OutputAction::WriteHtml(ref html) => {
+                    w.write_output_string(ecx, html);
                 },
 
                 &OutputAction::WriteResult(ref simple_expr) => {
-                    ecx.span_warn(DUMMY_SP, &format!("Writing output string for WriteResult"));
+                    debug!("Writing output string for WriteResult");
                     &simple_expr.write_output_strings(ecx, w);
                 },
 
-                &OutputAction::WriteOpen(ref element_type) => {
-                    w.write_output_string(ecx, &format!("<{}>", &element_type));
+                &OutputAction::WriteOpen(ref element_type, ref attributes, _) => {
+                    write_open_tag(ecx, w, element_type, attributes, false);
                 },
 
-                &OutputAction::WriteClose(ref element_type) => {
-                    w.write_output_string(ecx, &format!("</{}>", &element_type));
+                &OutputAction::WriteVoid(ref element_type, ref attributes, _) => {
+                    write_open_tag(ecx, w, element_type, attributes, true);
                 },
 
-                &OutputAction::WriteVoid(ref element_type) => {
-                    w.write_output_string(ecx, &format!("<{} />", &element_type));
+                &OutputAction::WriteClose(..) => {
+                    if let Some(html) = self.static_html() {
+                        w.write_output_string(ecx, &html);
+                    }
+                },
+
+                &OutputAction::Flush => {
+                    w.write_flush(ecx);
+                },
+
+                &OutputAction::Match(ref path, ref arms, _) => {
+                    let arms = arms.iter().map(|arm| {
+                        let mut output_strings: Vec<OutputString> = Vec::new();
+                        {
+                            let arm_w: &mut OutputStringWrite<Html> = &mut output_strings;
+                            for output_action in &coalesce_static_html(&arm.output_actions) {
+                                output_action.write_output_strings(ecx, arm_w);
+                            }
+                        }
+
+                        MatchArmStrings {
+                            variant: arm.variant.clone(),
+                            bindings: arm.bindings.clone(),
+                            output_strings: output_strings
+                        }
+                    }).collect();
+                    w.write_match(ecx, path, arms);
+                },
+
+                &OutputAction::Let(ref name, ref path, ref output_actions, _) => {
+                    let mut output_strings: Vec<OutputString> = Vec::new();
+                    {
+                        let let_w: &mut OutputStringWrite<Html> = &mut output_strings;
+                        for output_action in &coalesce_static_html(output_actions) {
+                            output_action.write_output_strings(ecx, let_w);
+                        }
+                    }
+                    w.write_let(ecx, name, path, output_strings);
                 }
             }
         }
@@ -91,66 +198,154 @@ impl OutputActionWrite for Vec<OutputAction> {
 }
 
 impl WriteJs for OutputAction {
-    //fn write_js<W>(&self, js: &mut W) where W: JsWrite {
-    fn write_js(&self, js: &mut JsWrite) {
+    fn write_js(&self, js: &mut JsWrite, backend: &JsBackend) {
+        if let Some(span) = self.span() {
+            js.source_span(span);
+        }
+
         match *self {
             OutputAction::Write(ref contents) => {
-                js.call_method("IncrementalDOM.text", &|pl| {
-                    pl.param(&|ex| {
-                        ex.string_lit(&contents);
-                    });
+                backend.text(js, &|ex| {
+                    ex.string_lit(&contents);
                 });
             },
 
             OutputAction::WriteResult(ref template_expr) => {
-                js.call_method("IncrementalDOM.text", &|pl| {
-                    pl.param(&|ex| {
-                        template_expr.write_js_simple_expr(ex);
-                    });
+                backend.text(js, &|ex| {
+                    template_expr.write_js_simple_expr(ex);
                 });
             },
 
-            OutputAction::WriteOpen(ref element_type) => {
-                js.call_method("IncrementalDOM.elementOpen", &|pl| {
-                    pl.param(&|ex| {
-                        ex.string_lit(&element_type);
-                    });
-                });
+            // Only merged for the HTML output; the JS keeps the actions it was merged from
+            OutputAction::WriteHtml(_) => {},
+
+            OutputAction::WriteOpen(ref element_type, ref attributes, _) => {
+                backend.element_open(js, element_type, attributes);
             },
 
-            OutputAction::WriteClose(ref element_type) => {
-                js.call_method("IncrementalDOM.elementClose", &|pl| {
-                    pl.param(&|ex| {
-                        ex.string_lit(&element_type);
-                    });
-                });
+            OutputAction::WriteClose(ref element_type, _) => {
+                backend.element_close(js, element_type);
             },
 
-            OutputAction::WriteVoid(ref element_type) => {
-                js.call_method("IncrementalDOM.elementVoid", &|pl| {
-                    pl.param(&|ex| {
-                        ex.string_lit(&element_type);
-                    });
-                });
+            OutputAction::WriteVoid(ref element_type, ref attributes, _) => {
+                backend.element_void(js, element_type, attributes);
+            },
+
+            // The client side renders all at once
+            OutputAction::Flush => {},
+
+            OutputAction::Match(ref path, ref arms, _) => {
+                write_js_match(path, arms, js, backend, &format!("{}-match", STATIC_KEY_PREFIX));
+            },
+
+            OutputAction::Let(ref name, ref path, ref output_actions, _) => {
+                write_js_let(name, path, output_actions, js, backend, &format!("{}-let", STATIC_KEY_PREFIX));
             }
         }
     }
 }
 
-impl WriteJs for Vec<OutputAction> {
-    fn write_js(&self, js: &mut JsWrite) {
-        for output_action in self {
-            output_action.write_js(js);
+/// Switch on the variant of the enum at `path`: `switch (load.tag) { case 'Loaded': { let data = load.values[0]; ... } }`.
+/// Static elements within an arm are keyed with `key_prefix`, as they are siblings of the elements around the match.
+fn write_js_match(path: &str, arms: &[MatchArmActions], js: &mut JsWrite, backend: &JsBackend, key_prefix: &str) {
+    js.switch_cases(&|ex| { ex.var_reference(&format!("{}.{}", path, ENUM_TAG)); }, &|cases| {
+        for (arm_idx, arm) in arms.iter().enumerate() {
+            cases.case_block(arm.variant.as_ref().map(|variant| variant.as_str()), &|js| {
+                for (idx, binding) in arm.bindings.iter().enumerate().filter(|&(_, binding)| binding != "_") {
+                    js.let_statement(binding, &|ex| { ex.var_reference(&format!("{}.{}[{}]", path, ENUM_VALUES, idx)); });
+                }
+                write_js_output_actions(&arm.output_actions, js, backend, &format!("{}-{}", key_prefix, arm_idx));
+            });
         }
+    });
+}
+
+/// Block declaring the local: `{ let city = data.profile.address.city; ... }`, which is only visible inside.
+/// Static elements within it are keyed with `key_prefix`, as for match arms.
+fn write_js_let(name: &str, path: &str, output_actions: &[OutputAction], js: &mut JsWrite, backend: &JsBackend, key_prefix: &str) {
+    js.block(&|js| {
+        js.let_statement(name, &|ex| { ex.var_reference(path); });
+        write_js_output_actions(output_actions, js, backend, key_prefix);
+    });
+}
+
+/// Write the actions of a view, with elements whose contents are static written through
+/// `JsBackend::static_element`, keyed by their position in the view
+fn write_js_output_actions(output_actions: &[OutputAction], js: &mut JsWrite, backend: &JsBackend, key_prefix: &str) {
+    let mut idx = 0;
+    while idx < output_actions.len() {
+        match output_actions[idx] {
+            OutputAction::WriteOpen(ref element_type, ref attributes, span) => {
+                if let Some(close) = static_element_end(output_actions, idx) {
+                    let contents = &output_actions[idx + 1..close];
+                    js.source_span(span);
+                    backend.static_element(js, element_type, attributes, &format!("{}-{}", key_prefix, idx), &|js| {
+                        for output_action in contents {
+                            output_action.write_js(js, backend);
+                        }
+                    });
+                    idx = close + 1;
+                    continue;
+                }
+            },
+
+            OutputAction::Match(ref path, ref arms, span) => {
+                js.source_span(span);
+                write_js_match(path, arms, js, backend, &format!("{}-{}", key_prefix, idx));
+                idx += 1;
+                continue;
+            },
+
+            OutputAction::Let(ref name, ref path, ref let_actions, span) => {
+                js.source_span(span);
+                write_js_let(name, path, let_actions, js, backend, &format!("{}-{}", key_prefix, idx));
+                idx += 1;
+                continue;
+            },
+
+            _ => {}
+        };
+
+        output_actions[idx].write_js(js, backend);
+        idx += 1;
+    }
+}
+
+impl WriteJs for Vec<OutputAction> {
+    fn write_js(&self, js: &mut JsWrite, backend: &JsBackend) {
+        write_js_output_actions(self, js, backend, STATIC_KEY_PREFIX);
     }
 }
 
 impl<S: WriteOutputActions> WriteJs for S {
-    fn write_js(&self, js: &mut JsWrite) {
+    fn write_js(&self, js: &mut JsWrite, backend: &JsBackend) {
         let mut output_actions = Vec::new();
         self.write_output_actions(&mut output_actions);
-        for output_action in &output_actions {
-            output_action.write_js(js);
-        }
+        write_js_output_actions(&output_actions, js, backend, STATIC_KEY_PREFIX);
     }
 }
+
+#[test]
+fn test_text_escaping() {
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions};
+    use js_backend::INCREMENTAL_DOM;
+    use optimize::coalesce_static_html;
+    use syntax::codemap::DUMMY_SP;
+
+    let output_actions = vec![
+        OutputAction::WriteOpen("p".to_owned(), vec![], DUMMY_SP),
+        OutputAction::Write("a < b && c".to_owned()),
+        OutputAction::WriteClose("p".to_owned(), DUMMY_SP)
+    ];
+
+    // The HTML has the text escaped once, also when merged with the markup around it
+    let coalesced: Vec<Option<String>> = coalesce_static_html(&output_actions).iter().map(|output_action| output_action.static_html()).collect();
+    assert_eq!(coalesced, vec![Some("<p>a &lt; b &amp;&amp; c</p>".to_owned())]);
+
+    // The JS sets the text itself
+    let mut out = String::new();
+    JsFormatter::new(&mut out, JsFormat::Minified).function("view", vec![], &|js| {
+        output_actions[1].write_js(js, &INCREMENTAL_DOM);
+    });
+    assert_eq!(out, "function view(){IncrementalDOM.text(\"a < b && c\");}");
+}
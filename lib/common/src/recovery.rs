@@ -0,0 +1,83 @@
+use syntax::parse::token;
+use syntax::parse::parser::Parser;
+
+
+/// Skip tokens until `stop` matches at the current nesting level, or the enclosing group ends.
+/// Neither the matching token nor the closing delimiter of the enclosing group are consumed.
+pub fn skip_until(parser: &mut Parser, stop: &Fn(&mut Parser) -> bool) {
+    let mut depth = 0usize;
+
+    loop {
+        if depth == 0 && stop(parser) {
+            return;
+        }
+
+        match parser.token {
+            token::Eof => { return; },
+            token::OpenDelim(_) => { depth += 1; },
+            token::CloseDelim(_) => {
+                if depth == 0 {
+                    return;
+                }
+                depth -= 1;
+            },
+            _ => {}
+        }
+        parser.bump();
+    }
+}
+
+/// Skip a single token, or a whole delimited group
+pub fn skip_token_tree(parser: &mut Parser) {
+    match parser.token {
+        token::Eof | token::CloseDelim(_) => {},
+        token::OpenDelim(_) => {
+            parser.bump();
+            skip_until(parser, &|_| false);
+            if parser.token != token::Eof {
+                parser.bump();
+            }
+        },
+        _ => { parser.bump(); }
+    }
+}
+
+fn is_keyword(token: &token::Token, keywords: &[&str]) -> bool {
+    match *token {
+        token::Ident(ident) => {
+            let name = ident.name.to_string();
+            keywords.iter().any(|keyword| *keyword == name)
+        },
+        _ => false
+    }
+}
+
+/// At the start of a template item, such as `view root [` or `store counter {`
+pub fn at_template_item(parser: &mut Parser) -> bool {
+    is_keyword(&parser.token, &["view", "store", "model"]) && parser.look_ahead(1, |t| t.is_ident())
+}
+
+/// At the start of a store item, such as `action INCREMENT` or `default =>`
+pub fn at_store_item(parser: &mut Parser) -> bool {
+    is_keyword(&parser.token, &["action", "default"])
+}
+
+/// At the start of an element, such as `div [` or `div(`
+pub fn at_element(parser: &mut Parser) -> bool {
+    parser.token.is_ident() && parser.look_ahead(1, |t| t == &token::OpenDelim(token::Bracket) || t == &token::OpenDelim(token::Paren))
+}
+
+/// At a flush point, `flush` on its own rather than an element named flush
+pub fn at_flush(parser: &mut Parser) -> bool {
+    is_keyword(&parser.token, &["flush"]) && !at_element(parser)
+}
+
+/// At the start of a match block, `match` followed by the value matched on
+pub fn at_match(parser: &mut Parser) -> bool {
+    is_keyword(&parser.token, &["match"]) && parser.look_ahead(1, |t| t.is_ident())
+}
+
+/// At the start of a let block, `let` followed by the name it binds
+pub fn at_let(parser: &mut Parser) -> bool {
+    is_keyword(&parser.token, &["let"]) && parser.look_ahead(1, |t| t.is_ident())
+}
@@ -0,0 +1,383 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use value::Value;
+
+
+/// Error writing rendered output
+#[derive(Debug)]
+pub enum RenderError {
+    Io(io::Error),
+    Fmt(fmt::Error),
+    /// The render function panicked while streaming
+    Aborted
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RenderError::Io(ref err) => write!(f, "error writing rendered output: {}", err),
+            &RenderError::Fmt(ref err) => write!(f, "error formatting rendered output: {}", err),
+            &RenderError::Aborted => write!(f, "rendering was aborted")
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn description(&self) -> &str {
+        match self {
+            &RenderError::Io(ref err) => err.description(),
+            &RenderError::Fmt(ref err) => err.description(),
+            &RenderError::Aborted => "rendering was aborted"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self {
+            &RenderError::Io(ref err) => Some(err),
+            &RenderError::Fmt(ref err) => Some(err),
+            &RenderError::Aborted => None
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> RenderError { RenderError::Io(err) }
+}
+
+impl From<fmt::Error> for RenderError {
+    fn from(err: fmt::Error) -> RenderError { RenderError::Fmt(err) }
+}
+
+pub type RenderResult = Result<(), RenderError>;
+
+/// Destination of the generated render functions, which write to it with `write!` as they go.
+/// Implemented for `String` and `Vec<u8>`; wrap other writers in `IoWrite` or `FmtWrite`,
+/// e.g. `IoWrite(response.start()?)` to stream a page to the HTTP response.
+pub trait RenderWrite {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult;
+
+    /// Write output known at compile time, such as the static parts of a view
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        self.write_fmt(format_args!("{}", s))
+    }
+
+    /// Send the output written so far, at a `flush` point in a view
+    fn flush_chunk(&mut self) -> RenderResult { Ok(()) }
+}
+
+impl RenderWrite for String {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+        Ok(try!(fmt::Write::write_fmt(self, args)))
+    }
+
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl RenderWrite for Vec<u8> {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+        Ok(try!(io::Write::write_fmt(self, args)))
+    }
+
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        self.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<'a, W: RenderWrite> RenderWrite for &'a mut W {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+        (**self).write_fmt(args)
+    }
+
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        (**self).write_static(s)
+    }
+
+    fn flush_chunk(&mut self) -> RenderResult {
+        (**self).flush_chunk()
+    }
+}
+
+/// Render into any `std::io::Write`, such as a file, socket or response body
+pub struct IoWrite<W: io::Write>(pub W);
+
+impl<W: io::Write> RenderWrite for IoWrite<W> {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+        Ok(try!(self.0.write_fmt(args)))
+    }
+
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        Ok(try!(self.0.write_all(s.as_bytes())))
+    }
+
+    fn flush_chunk(&mut self) -> RenderResult {
+        Ok(try!(self.0.flush()))
+    }
+}
+
+/// Render into any `std::fmt::Write`, such as a `fmt::Formatter`
+pub struct FmtWrite<W: fmt::Write>(pub W);
+
+impl<W: fmt::Write> RenderWrite for FmtWrite<W> {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+        Ok(try!(self.0.write_fmt(args)))
+    }
+
+    fn write_static(&mut self, s: &'static str) -> RenderResult {
+        Ok(try!(self.0.write_str(s)))
+    }
+}
+
+/// Escape text for HTML content or a double-quoted attribute value
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Write a value looked up from the state while rendering. Values are escaped, as they
+/// may hold user input, and are written both as text and as attribute values.
+pub fn write_html_text<W: RenderWrite>(w: &mut W, value: &Value) -> RenderResult {
+    w.write_fmt(format_args!("{}", escape_html(&value.to_string())))
+}
+
+/// Blocking adapter for streamed responses: the usual blocking render runs on a thread of a
+/// `BlockingRenderPool`, and its output is yielded in chunks split at the `flush` points of the
+/// views. There is no async render API; the render function holds its pool thread while it
+/// waits on data, so the pool bounds how many renders run at once.
+pub mod stream {
+    use std::io;
+    use std::fmt;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc as std_mpsc;
+    use std::thread;
+    use std::marker::PhantomData;
+    use futures::{Async, Future, Poll, Sink, Stream};
+    use futures::sync::{mpsc, oneshot};
+    use super::{RenderWrite, RenderError, RenderResult};
+
+    /// Writer sending the output written so far as a chunk at each flush point
+    pub struct ChunkWrite {
+        buf: Vec<u8>,
+        sender: Option<mpsc::Sender<Vec<u8>>>
+    }
+
+    impl RenderWrite for ChunkWrite {
+        fn write_fmt(&mut self, args: fmt::Arguments) -> RenderResult {
+            Ok(try!(io::Write::write_fmt(&mut self.buf, args)))
+        }
+
+        fn write_static(&mut self, s: &'static str) -> RenderResult {
+            self.buf.extend_from_slice(s.as_bytes());
+            Ok(())
+        }
+
+        /// Blocks while the previous chunk has not been taken from the stream
+        fn flush_chunk(&mut self) -> RenderResult {
+            if self.buf.is_empty() {
+                return Ok(());
+            }
+
+            let chunk = ::std::mem::replace(&mut self.buf, Vec::new());
+            let sender = match self.sender.take() {
+                Some(sender) => sender,
+                None => { return Err(RenderError::Aborted); }
+            };
+
+            match sender.send(chunk).wait() {
+                Ok(sender) => {
+                    self.sender = Some(sender);
+                    Ok(())
+                },
+                Err(_) => Err(RenderError::Io(io::Error::new(io::ErrorKind::BrokenPipe, "the chunk stream was dropped")))
+            }
+        }
+    }
+
+    /// Chunks of rendered output, ending with the error of the render function if it failed
+    pub struct ChunkStream {
+        chunks: mpsc::Receiver<Vec<u8>>,
+        result: oneshot::Receiver<RenderResult>
+    }
+
+    /// Render waiting for a pool thread; `FnOnce` can't be called boxed, so it is taken out once
+    type RenderJob = Box<FnMut() + Send>;
+
+    /// Fixed set of threads running blocking renders. Renders started while all threads are busy
+    /// wait for one to finish, and their streams are not ready meanwhile.
+    pub struct BlockingRenderPool {
+        jobs: Mutex<std_mpsc::Sender<RenderJob>>
+    }
+
+    impl BlockingRenderPool {
+        pub fn new(threads: usize) -> BlockingRenderPool {
+            let (sender, receiver) = std_mpsc::channel::<RenderJob>();
+            let receiver = Arc::new(Mutex::new(receiver));
+
+            for _ in 0..threads.max(1) {
+                let receiver = receiver.clone();
+                thread::spawn(move || {
+                    loop {
+                        // The lock is released before running the job
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            // A panicking render ends its stream with `RenderError::Aborted`, not the thread
+                            Ok(mut job) => { let _ = panic::catch_unwind(AssertUnwindSafe(|| job())); },
+                            // The pool was dropped, and every render it started has run
+                            Err(_) => { break; }
+                        }
+                    }
+                });
+            }
+
+            BlockingRenderPool { jobs: Mutex::new(sender) }
+        }
+
+        /// Run `render` on a pool thread, streaming its output. The render function may block on
+        /// data it needs for later sections (e.g. with `Future::wait`) once earlier chunks are flushed,
+        /// and blocks while the previous chunk has not been taken from the stream.
+        pub fn render_chunks<F>(&self, render: F) -> ChunkStream
+            where F: FnOnce(&mut ChunkWrite) -> RenderResult + Send + 'static
+        {
+            let (sender, chunks) = mpsc::channel(1);
+            let (result_sender, result) = oneshot::channel();
+
+            let mut render = Some((render, sender, result_sender));
+            let job: RenderJob = Box::new(move || {
+                if let Some((render, sender, result_sender)) = render.take() {
+                    let mut w = ChunkWrite { buf: Vec::new(), sender: Some(sender) };
+                    let rendered = render(&mut w).and_then(|_| w.flush_chunk());
+                    // Ends the chunks before the result is taken
+                    drop(w);
+                    let _ = result_sender.complete(rendered);
+                }
+            });
+            // The pool threads only stop once the pool is dropped, so the job is always received
+            let _ = self.jobs.lock().unwrap().send(job);
+
+            ChunkStream { chunks: chunks, result: result }
+        }
+    }
+
+    impl Stream for ChunkStream {
+        type Item = Vec<u8>;
+        type Error = RenderError;
+
+        fn poll(&mut self) -> Poll<Option<Vec<u8>>, RenderError> {
+            match self.chunks.poll() {
+                Ok(Async::Ready(Some(chunk))) => { return Ok(Async::Ready(Some(chunk))); },
+                Ok(Async::NotReady) => { return Ok(Async::NotReady); },
+                Ok(Async::Ready(None)) | Err(_) => {}
+            };
+
+            match self.result.poll() {
+                Ok(Async::Ready(Ok(()))) => Ok(Async::Ready(None)),
+                Ok(Async::Ready(Err(err))) => Err(err),
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                // The render thread panicked
+                Err(_) => Err(RenderError::Aborted)
+            }
+        }
+    }
+
+    impl ChunkStream {
+        /// Adapt to a response body stream, such as hyper's `Body` of `Chunk`s:
+        /// `chunks.into_body::<hyper::Chunk, hyper::Error>(|err| hyper::Error::Io(...))`
+        pub fn into_body<C: From<Vec<u8>>, E>(self, map_err: fn(RenderError) -> E) -> Body<C, E> {
+            Body { chunks: self, map_err: map_err, _c: PhantomData }
+        }
+    }
+
+    /// Response body of rendered chunks
+    pub struct Body<C, E> {
+        chunks: ChunkStream,
+        map_err: fn(RenderError) -> E,
+        _c: PhantomData<C>
+    }
+
+    impl<C: From<Vec<u8>>, E> Stream for Body<C, E> {
+        type Item = C;
+        type Error = E;
+
+        fn poll(&mut self) -> Poll<Option<C>, E> {
+            match self.chunks.poll() {
+                Ok(Async::Ready(chunk)) => Ok(Async::Ready(chunk.map(C::from))),
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Err(err) => Err((self.map_err)(err))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render_chunks() {
+    use futures::Stream;
+
+    let pool = stream::BlockingRenderPool::new(1);
+    let chunks = pool.render_chunks(|w| {
+        try!(write!(w, "<head></head>"));
+        try!(w.flush_chunk());
+        write!(w, "<body></body>")
+    });
+
+    let body: Vec<String> = chunks.into_body::<Vec<u8>, RenderError>(|err| err).wait()
+        .map(|chunk| String::from_utf8(chunk.unwrap()).unwrap())
+        .collect();
+    assert_eq!(body, vec!["<head></head>", "<body></body>"]);
+}
+
+#[test]
+fn test_write_html_text() {
+    let mut out = String::new();
+    write_html_text(&mut out, &Value::from("<script>alert(\"x\")</script>")).unwrap();
+    assert_eq!(out, "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;");
+
+    let mut out = String::new();
+    write_html_text(&mut out, &Value::from(3i64)).unwrap();
+    assert_eq!(out, "3");
+}
+
+#[test]
+fn test_render_errors() {
+    use futures::Stream;
+
+    struct ClosedWrite;
+    impl io::Write for ClosedWrite {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed")) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    match write!(IoWrite(ClosedWrite), "<p>") {
+        Err(RenderError::Io(ref err)) => { assert_eq!(err.kind(), io::ErrorKind::BrokenPipe); },
+        result => { panic!("expected an io error, got {:?}", result); }
+    };
+
+    // A failed render ends the stream with its error, after the chunks flushed before it
+    let pool = stream::BlockingRenderPool::new(1);
+    let chunks = pool.render_chunks(|w| {
+        try!(write!(w, "<head></head>"));
+        try!(w.flush_chunk());
+        Err(RenderError::Fmt(fmt::Error))
+    });
+    let results: Vec<Result<Vec<u8>, RenderError>> = chunks.wait().take(2).collect();
+    assert_eq!(results[0].as_ref().ok(), Some(&b"<head></head>".to_vec()));
+    match results[1] {
+        Err(RenderError::Fmt(_)) => {},
+        ref result => { panic!("expected the render error, got {:?}", result); }
+    };
+}
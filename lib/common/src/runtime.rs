@@ -0,0 +1,208 @@
+use config::{Config, JsModuleFormat};
+use js_backend::JsBackendKind;
+
+
+/// Store implementation the generated JS is wired to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StoreRuntime {
+    /// Redux, loaded separately by the page (the default)
+    Redux,
+    /// Minimal store emitted with the generated JS, without external dependencies
+    BuiltIn
+}
+
+impl StoreRuntime {
+    pub fn from_name(name: &str) -> Option<StoreRuntime> {
+        match name {
+            "redux" => Some(StoreRuntime::Redux),
+            "builtin" => Some(StoreRuntime::BuiltIn),
+            _ => None
+        }
+    }
+
+    /// JS function creating a store from a reducer
+    pub fn create_store_fn(&self) -> &'static str {
+        match *self {
+            StoreRuntime::Redux => "Redux.createStore",
+            StoreRuntime::BuiltIn => "IncrustStore.createStore"
+        }
+    }
+
+    /// JS source to emit along with the generated functions, if any
+    pub fn js_source(&self) -> Option<&'static str> {
+        match *self {
+            StoreRuntime::Redux => None,
+            StoreRuntime::BuiltIn => Some(BUILTIN_STORE_JS)
+        }
+    }
+
+    /// ES module import for the store, as binding and module specifier
+    pub fn es_import(&self, runtime_module: &str) -> (String, String) {
+        match *self {
+            StoreRuntime::Redux => ("* as Redux".to_owned(), "redux".to_owned()),
+            StoreRuntime::BuiltIn => ("{ IncrustStore }".to_owned(), runtime_module.to_owned())
+        }
+    }
+}
+
+impl Default for StoreRuntime {
+    fn default() -> StoreRuntime { StoreRuntime::Redux }
+}
+
+/// The built-in store: `createStore(reducer, initialState)` returning an object with
+/// `getState`, `dispatch` and `subscribe`, compatible with the subset of Redux used by generated code.
+/// Defined once, even when several templates include it.
+pub const BUILTIN_STORE_JS: &'static str = "var IncrustStore = IncrustStore || (function() {\
+function createStore(reducer, initialState) {\
+var state = reducer(initialState, { type: '@@incrust/INIT' });\
+var listeners = [];\
+return {\
+getState: function() { return state; },\
+dispatch: function(action) { state = reducer(state, action); listeners.slice().forEach(function(listener) { listener(); }); return action; },\
+subscribe: function(listener) { listeners.push(listener); return function() { var idx = listeners.indexOf(listener); if (idx >= 0) { listeners.splice(idx, 1); } }; }\
+};\
+}\
+return { createStore: createStore };\
+})();";
+
+/// Source of the built-in runtime as an ES module, to be served at the configured `runtime_module`
+pub fn es_runtime_module() -> String {
+    let mut source = String::new();
+    source.push_str(BUILTIN_STORE_JS);
+    let mut exports = vec!["IncrustStore".to_owned()];
+
+    for kind in JsBackendKind::all() {
+        let backend = kind.backend();
+        if let Some(backend_source) = backend.js_source() {
+            source.push_str(backend_source);
+            exports.push(backend.runtime_object().to_owned());
+        }
+    }
+
+    source.push_str(&format!("export {{ {} }};", exports.join(", ")));
+    source
+}
+
+/// Runtime support for the generated JS of a template: the sources of the store and rendering backend,
+/// and a function rendering a view into a root element with the configured backend.
+#[derive(Clone, Debug)]
+pub struct Runtime {
+    template_name: String,
+    store_runtime: StoreRuntime,
+    js_backend: JsBackendKind,
+    js_module: JsModuleFormat,
+    runtime_module: String
+}
+
+impl Runtime {
+    pub fn new(template_name: &str, config: &Config) -> Runtime {
+        Runtime {
+            template_name: template_name.to_owned(),
+            store_runtime: config.store_runtime,
+            js_backend: config.js_backend,
+            js_module: config.js_module,
+            runtime_module: config.runtime_module.to_owned()
+        }
+    }
+
+    pub fn store_runtime(&self) -> StoreRuntime { self.store_runtime }
+    pub fn js_backend(&self) -> JsBackendKind { self.js_backend }
+}
+
+mod output {
+    use super::Runtime;
+    use config::JsModuleFormat;
+    use js_write::{WriteJsFunctions, JsWriteFunctions};
+    use js_backend::JsBackend;
+    use codegen::lang::Js;
+    use codegen::named_output::{NamedOutput, NamedOutputType};
+    use codegen::naming::patch_fn_name;
+
+    impl WriteJsFunctions for Runtime {
+        /// Written for the backend the runtime was created with, which is the one views are written for
+        fn write_js_functions(&self, funcs: &mut JsWriteFunctions, _: &JsBackend) {
+            let backend = self.js_backend.backend();
+
+            match self.js_module {
+                JsModuleFormat::Global => {
+                    if let Some(source) = self.store_runtime.js_source() {
+                        funcs.source(source);
+                    }
+                    if let Some(source) = backend.js_source() {
+                        funcs.source(source);
+                    }
+                },
+
+                // Imports are hoisted, so they apply to the view and store functions of the module as well
+                JsModuleFormat::Es => {
+                    let (binding, module) = self.store_runtime.es_import(&self.runtime_module);
+                    funcs.import_module(&binding, &module);
+                    let (binding, module) = backend.es_import(&self.runtime_module);
+                    funcs.import_module(&binding, &module);
+                }
+            };
+
+            if let Some(source) = backend.js_setup() {
+                funcs.source(source);
+            }
+
+            // The backends render views from the state alone, so pass the dispatch along
+            funcs.function(&patch_fn_name(&self.template_name), vec!["root", "view", "state", "dispatch"], &|js| {
+                js.return_expr(&|js_simple| {
+                    js_simple.call_params(backend.patch_fn(), &|pl| {
+                        pl.param(&|ex| { ex.var_reference("root"); });
+                        pl.param(&|ex| {
+                            ex.function_expr(vec!["state"], &|js| {
+                                js.call_method("view", &|pl| {
+                                    pl.param(&|ex| { ex.var_reference("state"); });
+                                    pl.param(&|ex| { ex.var_reference("dispatch"); });
+                                });
+                            });
+                        });
+                        pl.param(&|ex| { ex.var_reference("state"); });
+                    });
+                });
+            });
+        }
+    }
+
+    impl NamedOutput<Js> for Runtime {
+        fn output_name(&self) -> &str { &self.template_name }
+        fn output_type(&self) -> NamedOutputType { NamedOutputType::RuntimeOutput }
+    }
+}
+
+#[test]
+fn test_builtin_store_runtime() {
+    use js_write::output_strings::js_functions_output;
+    use nodes::store_node::RootStore;
+
+    let config = Config { store_runtime: StoreRuntime::BuiltIn, ..Config::default() };
+    let (out, _) = js_functions_output(&Runtime::new("main", &config), &config);
+    assert!(out.starts_with(BUILTIN_STORE_JS));
+
+    let (out, _) = js_functions_output(&RootStore::new("main", StoreRuntime::BuiltIn, vec![]), &config);
+    assert!(out.contains("return IncrustStore.createStore(rusttemplate_store_template_main, initial_state);"));
+
+    // Redux is loaded by the page
+    let config = Config::default();
+    let (out, _) = js_functions_output(&Runtime::new("main", &config), &config);
+    assert!(!out.contains("IncrustStore"));
+}
+
+#[test]
+fn test_es_module_output() {
+    use js_write::output_strings::js_functions_output;
+    use nodes::store_node::RootStore;
+
+    let config = Config { store_runtime: StoreRuntime::BuiltIn, js_backend: JsBackendKind::Dom, js_module: JsModuleFormat::Es, ..Config::default() };
+    let (out, _) = js_functions_output(&Runtime::new("main", &config), &config);
+    assert!(out.starts_with("import { IncrustStore } from './incrust_runtime.js';\nimport { IncrustDom } from './incrust_runtime.js';\n"));
+    assert!(out.contains("\nexport function rusttemplate_patch_template_main(root, view, state, dispatch) {"));
+    assert!(!out.contains("var IncrustDom"));
+
+    let (out, _) = js_functions_output(&RootStore::new("main", StoreRuntime::BuiltIn, vec![]), &config);
+    assert!(out.starts_with("export function rusttemplate_store_template_main(state, action) {"));
+
+    assert!(es_runtime_module().ends_with("export { IncrustStore, IncrustDom, IncrustVdom, IncrustString };"));
+}
@@ -1,5 +1,6 @@
 
 use syntax::codemap::{Span, DUMMY_SP};
+use codegen::output_string_writer::OutputExpr;
 
 
 pub trait WriteSimpleExpr {
@@ -15,6 +16,8 @@ pub enum SimpleExprNumber {
 #[derive(Clone, Debug)]
 pub enum SimpleExprToken {
     VarReference(String),
+    /// Reference into state declared as `Any`, resolved by path when rendering
+    DynamicReference(String),
     LitString(String),
     LitNumber(SimpleExprNumber),
     OpenBrace,
@@ -35,6 +38,115 @@ impl SimpleExpr {
     pub fn tokens(&self) -> &[SimpleExprToken] {
         &self.tokens
     }
+
+    /// Span of the expression within the template source
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Text of an expression which is a single literal, known at compile time
+    pub fn static_string(&self) -> Option<String> {
+        if self.tokens.len() != 1 {
+            return None;
+        }
+        match &self.tokens[0] {
+            &SimpleExprToken::LitString(ref contents) => Some(contents.to_owned()),
+            &SimpleExprToken::LitNumber(SimpleExprNumber::Int64(n)) => Some(n.to_string()),
+            &SimpleExprToken::LitNumber(SimpleExprNumber::Int32(n)) => Some(n.to_string()),
+            _ => None
+        }
+    }
+
+    /// Path of an expression which is a single reference, such as `person.first_name`
+    pub fn reference_path(&self) -> Option<&str> {
+        if self.tokens.len() != 1 {
+            return None;
+        }
+        match &self.tokens[0] {
+            &SimpleExprToken::VarReference(ref var_name) |
+            &SimpleExprToken::DynamicReference(ref var_name) => Some(var_name),
+            _ => None
+        }
+    }
+
+    /// The expression as evaluated when rendering on the server, or `None` if it is malformed,
+    /// which the type checker reports
+    pub fn output_expr(&self) -> Option<OutputExpr> {
+        let mut pos = 0;
+        let expr = output_expr_from(&self.tokens, &mut pos);
+        if pos < self.tokens.len() { None } else { expr }
+    }
+
+    /// Mark references at or below any of the given (`Any` typed) paths as dynamic
+    pub fn resolve_dynamic_references(&mut self, roots: &[String]) {
+        for token in &mut self.tokens {
+            let dynamic = match token {
+                &mut SimpleExprToken::VarReference(ref var_name) => {
+                    let is_dynamic = roots.iter().any(|root| var_name == root || var_name.starts_with(&format!("{}.", root)));
+                    if is_dynamic { Some(var_name.to_owned()) } else { None }
+                },
+                _ => None
+            };
+
+            if let Some(var_name) = dynamic {
+                *token = SimpleExprToken::DynamicReference(var_name);
+            }
+        }
+    }
+}
+
+/// Operands joined by `+` and `-`, left to right, as in `TypeChecker::infer_expr`
+fn output_expr_from(tokens: &[SimpleExprToken], pos: &mut usize) -> Option<OutputExpr> {
+    let mut expr = match output_operand_from(tokens, pos) {
+        Some(expr) => expr,
+        None => { return None; }
+    };
+
+    while *pos < tokens.len() {
+        let plus = match tokens[*pos] {
+            SimpleExprToken::BinopPlus => true,
+            SimpleExprToken::BinopMinus => false,
+            _ => break
+        };
+        *pos += 1;
+
+        let rhs = match output_operand_from(tokens, pos) {
+            Some(rhs) => rhs,
+            None => { return None; }
+        };
+        expr = if plus { OutputExpr::Plus(Box::new(expr), Box::new(rhs)) } else { OutputExpr::Minus(Box::new(expr), Box::new(rhs)) };
+    }
+
+    Some(expr)
+}
+
+fn output_operand_from(tokens: &[SimpleExprToken], pos: &mut usize) -> Option<OutputExpr> {
+    let token = match tokens.get(*pos) {
+        Some(token) => token,
+        None => { return None; }
+    };
+    *pos += 1;
+
+    match token {
+        &SimpleExprToken::VarReference(ref var_name) |
+        &SimpleExprToken::DynamicReference(ref var_name) => Some(OutputExpr::Lookup(var_name.to_owned())),
+        &SimpleExprToken::LitString(ref contents) => Some(OutputExpr::String(contents.to_owned())),
+        &SimpleExprToken::LitNumber(SimpleExprNumber::Int64(n)) => Some(OutputExpr::Number(n)),
+        &SimpleExprToken::LitNumber(SimpleExprNumber::Int32(n)) => Some(OutputExpr::Number(n as i64)),
+
+        &SimpleExprToken::OpenParen => {
+            let expr = match output_expr_from(tokens, pos) {
+                Some(expr) => expr,
+                None => { return None; }
+            };
+            match tokens.get(*pos) {
+                Some(&SimpleExprToken::CloseParen) => { *pos += 1; Some(expr) },
+                _ => None
+            }
+        },
+
+        _ => None
+    }
 }
 
 pub trait ToSimpleExprTokens {
@@ -96,21 +208,22 @@ impl SimpleExprWrite for Vec<SimpleExprToken> {
 
 pub mod parse {
     use syntax::ast::{self, LitKind, LitIntType, IntTy};
-    use syntax::codemap::{Span, DUMMY_SP};
+    use syntax::codemap::{Span, DUMMY_SP, BytePos, mk_sp};
     use syntax::parse::token::DelimToken;
     use syntax::parse::token::BinOpToken as binops;
     use syntax::parse::token::Lit as literals;
     use syntax::ext::base::ExtCtxt;
-    use syntax::parse::{token, PResult};
+    use syntax::parse::{token, PResult, new_parser_from_source_str};
     use syntax::parse::parser::Parser;
     use super::{SimpleExpr, SimpleExprToken, SimpleExprNumber, SimpleExprWrite};
+    use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticWrite, unexpected_token};
 
     fn parse_var_reference<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span) -> PResult<'a, SimpleExprToken> {
         // NEXTREV: Add JsPathExpr variant
 
         let mut var_name = String::new();
         loop {
-            ecx.span_warn(span, &format!("Parsing var reference - token: {:?}", &parser.token));
+            debug!("Parsing var reference - token: {:?}", &parser.token);
 
             match parser.token {
                 token::Ident(ref ident) => {
@@ -120,34 +233,34 @@ pub mod parse {
                     var_name.push('.');
                 },
                 _ => {
-                    ecx.span_warn(span, "Invalid token in VarReference - complete");
+                    debug!("Invalid token in VarReference - complete");
                     break;
                 }
             }
             parser.bump();
         }
-        ecx.span_warn(span, &format!("Parsing var reference - complete: {:?}", &var_name));
+        debug!("Parsing var reference - complete: {:?}", &var_name);
         Ok(SimpleExprToken::VarReference(var_name.to_owned()))
     }
 
     fn parse_expr_contents_into_until<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, w: &mut SimpleExprWrite, end_cond: &Fn(&token::Token) -> bool) -> PResult<'a, ()> {
         loop {
-            ecx.span_warn(span, &format!("Parsing expression contents - token: {:?}", &parser.token));
+            debug!("Parsing expression contents - token: {:?}", &parser.token);
 
             if end_cond(&parser.token) {
-                    ecx.span_warn(span, &format!("Got close [{:?}] - completed expression", &parser.token));
+                    debug!("Got close [{:?}] - completed expression", &parser.token);
                     break;
             }
 
             match parser.token {
                 token::Ident(_) => {
-                    ecx.span_warn(span, &format!("Got ident - parsing var reference"));
+                    debug!("Got ident - parsing var reference");
                     
                     match parse_var_reference(ecx, parser, span) {
                         Ok(SimpleExprToken::VarReference(ref var_name)) => {
                             w.var_reference(var_name);
                         },
-                        _ => { ecx.span_warn(span, "Unable to parse VarReference for this ident"); }
+                        _ => { debug!("Unable to parse VarReference for this ident"); }
                     };
                     // Don't bump
                     continue;
@@ -158,12 +271,12 @@ pub mod parse {
                     match &lit.node {
                         &LitKind::Str(ref s, _) => {
                             let string_value = s.to_string();
-                            ecx.span_warn(span, &format!("Parsing simple expression - got literal string: {}", &string_value));
+                            debug!("Parsing simple expression - got literal string: {}", &string_value);
                             w.string_lit(&string_value);
                         },
 
                         &LitKind::Int(n, int_ty) => {
-                            ecx.span_warn(span, &format!("Parsing simple expression - got literal int ({:?}): {}", int_ty, n));
+                            debug!("Parsing simple expression - got literal int ({:?}): {}", int_ty, n);
                             match int_ty {
                                 LitIntType::Signed(IntTy::I64) => {
                                     w.number_lit(&SimpleExprNumber::Int64(n as i64));
@@ -179,13 +292,17 @@ pub mod parse {
                                 },
 
                                 _ => {
-                                    ecx.span_warn(span, &format!("Parsing simple expression - got unsupported number ({:?}): {:?}", int_ty, n));
+                                    Diagnostic::warning(DiagnosticCode::UnsupportedLiteral, lit.span, &format!("unsupported number type `{:?}`, the value will be ignored", int_ty))
+                                        .help("use an unsuffixed, i32 or i64 number")
+                                        .emit(parser.diagnostic());
                                 }
                             }
                         },
 
                         _ => {
-                            ecx.span_warn(span, &format!("Parsing simple expression - got unsupported literal: {:?}", &lit.node));
+                            Diagnostic::warning(DiagnosticCode::UnsupportedLiteral, lit.span, "unsupported literal, the value will be ignored")
+                                .help("only string and integer literals are supported in template expressions")
+                                .emit(parser.diagnostic());
                         }
                     }
                     // Don't bump, as we already parsed the literal
@@ -209,7 +326,7 @@ pub mod parse {
                 },
 
                 _ => {
-                    ecx.span_err(span, &format!("Parsing simple expression - unknown token: {:?}", &parser.token));
+                    return Err(unexpected_token(parser, "expression", "a name, literal, `+`, `-` or parentheses"));
                 }
             }
             parser.bump();
@@ -218,21 +335,49 @@ pub mod parse {
         Ok(())
     }
 
+    /// Span covering the tokens consumed since `lo`, or the current token if none were
+    fn expr_span(parser: &Parser, lo: BytePos) -> Span {
+        if parser.prev_span.hi > lo {
+            mk_sp(lo, parser.prev_span.hi)
+        } else {
+            parser.span
+        }
+    }
+
     pub fn parse_simple_expr_until<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, end_cond: &Fn(&token::Token) -> bool) -> PResult<'a, SimpleExpr> {
+        let lo = parser.span.lo;
         let mut tokens = Vec::new();
         try!(parse_expr_contents_into_until(ecx, &mut parser, span, &mut tokens, end_cond));
 
-        let simple_expr = SimpleExpr { span: span, tokens: tokens };
+        let simple_expr = SimpleExpr { span: expr_span(parser, lo), tokens: tokens };
         Ok(simple_expr)
     }
 
     pub fn parse_simple_expr<'cx, 'a>(ecx: &'cx ExtCtxt, mut parser: &mut Parser<'a>, span: Span, end_delim: DelimToken) -> PResult<'a, SimpleExpr> {
+        let lo = parser.span.lo;
         let mut tokens = Vec::new();
         try!(parse_expr_contents_into_until(ecx, &mut parser, span, &mut tokens, &|token| token == &token::CloseDelim(end_delim)));
 
-        let simple_expr = SimpleExpr { span: span, tokens: tokens };
+        let simple_expr = SimpleExpr { span: expr_span(parser, lo), tokens: tokens };
         Ok(simple_expr)
     }
+
+    /// Parse an expression interpolated into text, such as `counter` in `"Counter: {counter}"`.
+    /// The expression has no source of its own, so it is reported at the span of the text.
+    pub fn parse_interpolated_expr<'cx>(ecx: &'cx ExtCtxt, source: &str, span: Span, diagnostics: &mut DiagnosticWrite) -> Option<SimpleExpr> {
+        let mut parser = new_parser_from_source_str(ecx.parse_sess, ecx.cfg(), "<interpolation>".to_owned(), source.to_owned());
+        let mut tokens = Vec::new();
+
+        match parse_expr_contents_into_until(ecx, &mut parser, span, &mut tokens, &|token| token == &token::Eof) {
+            Ok(()) => Some(SimpleExpr { span: span, tokens: tokens }),
+            Err(err) => {
+                let error = Diagnostic::from_builder(err);
+                diagnostics.write_diagnostic(
+                    Diagnostic::error(DiagnosticCode::UnexpectedToken, span, &format!("invalid expression `{}` in text: {}", source, error.message())));
+                None
+            }
+        }
+    }
 }
 
 pub mod output_ast {
@@ -245,7 +390,7 @@ pub mod output_ast {
     use syntax::parse::{token, PResult};
 
     use super::{SimpleExpr, SimpleExprToken};
-    use js_write::{WriteJs, WriteJsSimpleExpr, JsWriteSimpleExpr};
+    use js_write::{WriteJs, WriteJsSimpleExpr, JsWriteSimpleExpr, JsFormatter, JsFormat};
     use codegen::IntoWriteStmt;
 
     impl ToTokens for SimpleExpr {
@@ -262,7 +407,7 @@ pub mod output_ast {
     impl IntoWriteStmt for SimpleExpr {
         fn into_write_stmt<'cx>(&self, ecx: &'cx ExtCtxt, w: ast::Ident) -> ast::Stmt {
             let mut contents = String::new();
-            &self.write_js_simple_expr(&mut contents);
+            &self.write_js_simple_expr(&mut JsFormatter::new(&mut contents, JsFormat::Pretty));
 
             let stmt = quote_stmt!(ecx, {
                     println!("Writing contents [{}] to ${}", $contents, "out");
@@ -275,59 +420,35 @@ pub mod output_ast {
 }
 
 mod output_strings {
-    use super::{SimpleExpr, SimpleExprToken, SimpleExprNumber};
-    use syntax::codemap::DUMMY_SP;
+    use super::SimpleExpr;
     use syntax::ext::base::ExtCtxt;
-    use codegen::lang::{Lang, Js, Html};
-    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
-    use output_actions::OutputAction;
+    use codegen::lang::Html;
+    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite, OutputExpr};
+    use render::escape_html;
 
     impl WriteOutputStrings<Html> for SimpleExpr {
         fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Html>) {
-            let mut s = String::new();
-            for token in &self.tokens {
-                ecx.span_warn(DUMMY_SP, &format!("Writing token: {:?}", &token));
-                match token {
-                    &SimpleExprToken::VarReference(ref var_name) => {
-                        w.write_output_string(ecx, &format!("{}", var_name));
-                    },
-
-                    &SimpleExprToken::LitString(ref contents) => {
-                        w.write_output_string(ecx, &format!("\"{}\"", contents));
-                    },
-
-                    &SimpleExprToken::LitNumber(ref contents) => {
-                        let s = match contents {
-                            &SimpleExprNumber::Int64(n) => format!("{}", n),
-                            &SimpleExprNumber::Int32(n) => format!("{}", n)
-                        };
-                        w.write_output_string(ecx, &s);
-                    },
-
-                    &SimpleExprToken::OpenBrace => {
-                        w.write_output_string(ecx, &format!("{{"));
-                    },
-
-                    &SimpleExprToken::CloseBrace => {
-                        w.write_output_string(ecx, &format!("}}"));
-                    },
+            match self.output_expr() {
+                // References are rooted at a store, and read from its slice of the root state
+                Some(OutputExpr::Lookup(ref var_name)) => {
+                    w.write_dynamic_lookup(ecx, var_name);
+                },
 
-                    &SimpleExprToken::OpenParen => {
-                        w.write_output_string(ecx, &format!("("));
-                    },
+                // Literals are known statically, and written as the text they evaluate to
+                Some(OutputExpr::String(ref contents)) => {
+                    w.write_output_string(ecx, &escape_html(contents));
+                },
 
-                    &SimpleExprToken::CloseParen => {
-                        w.write_output_string(ecx, &format!(")"));
-                    },
+                Some(OutputExpr::Number(n)) => {
+                    w.write_output_string(ecx, &n.to_string());
+                },
 
-                    &SimpleExprToken::BinopPlus => {
-                        w.write_output_string(ecx, &format!("+"));
-                    },
+                Some(expr) => {
+                    w.write_expr(ecx, expr);
+                },
 
-                    &SimpleExprToken::BinopMinus => {
-                        w.write_output_string(ecx, &format!("-"));
-                    }
-                }
+                // Malformed expressions are reported by the type checker
+                None => {}
             }
         }
     }
@@ -345,6 +466,11 @@ pub mod js_write {
                         js.var_reference(var_name);
                     },
 
+                    // Passed through to JS unchecked
+                    &SimpleExprToken::DynamicReference(ref var_name) => {
+                        js.var_reference(var_name);
+                    },
+
                     &SimpleExprToken::LitString(ref contents) => {
                         js.string_lit(contents);
                     },
@@ -383,4 +509,33 @@ pub mod js_write {
             }
         }   
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_html_output_strings() {
+    use testing::with_ecx;
+    use codegen::lang::Html;
+    use codegen::output_string_writer::{WriteOutputStrings, OutputString};
+
+    fn output_strings(tokens: Vec<SimpleExprToken>) -> Vec<OutputString> {
+        let mut output_strings: Vec<OutputString> = vec![];
+        with_ecx(|ecx| {
+            let expr = SimpleExpr { span: DUMMY_SP, tokens: tokens };
+            WriteOutputStrings::<Html>::write_output_strings(&expr, ecx, &mut output_strings);
+        });
+        output_strings
+    }
+
+    // Literals are written as their text, not as source
+    assert_eq!(output_strings(vec![SimpleExprToken::LitString("<x>".to_owned())]), vec![OutputString::Static("&lt;x&gt;".to_owned())]);
+
+    // Operators are evaluated when rendering
+    let counter = SimpleExprToken::VarReference("counter".to_owned());
+    let one = SimpleExprToken::LitNumber(SimpleExprNumber::Int64(1));
+    assert_eq!(output_strings(vec![counter.clone(), SimpleExprToken::BinopPlus, one.clone()]), vec![OutputString::Expr(
+        OutputExpr::Plus(Box::new(OutputExpr::Lookup("counter".to_owned())), Box::new(OutputExpr::Number(1))))]);
+    assert_eq!(output_strings(vec![counter.clone(), SimpleExprToken::BinopMinus, SimpleExprToken::OpenParen, one.clone(), SimpleExprToken::CloseParen]), vec![OutputString::Expr(
+        OutputExpr::Minus(Box::new(OutputExpr::Lookup("counter".to_owned())), Box::new(OutputExpr::Number(1))))]);
+
+    // Malformed expressions are left to the type checker
+    assert!(output_strings(vec![counter, SimpleExprToken::BinopPlus]).is_empty());
+}
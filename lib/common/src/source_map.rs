@@ -0,0 +1,299 @@
+use syntax::codemap::{BytePos, CodeMap, Span, mk_sp};
+
+
+/// Delimiters of span markers in generated JS. Control characters never appear in
+/// the generated source otherwise, so the markers can be found and stripped after writing.
+const MARKER_START: char = '\u{1}';
+const MARKER_END: char = '\u{2}';
+
+const BASE64_CHARS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How source maps for the generated JS are emitted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceMapMode {
+    /// No source map (the default)
+    Off,
+    /// Source map embedded in the bundle as a data URL
+    Inline,
+    /// Source map rendered as a separate output, referenced from the bundle as `{template}.js.map`
+    File
+}
+
+impl SourceMapMode {
+    pub fn from_name(name: &str) -> Option<SourceMapMode> {
+        match name {
+            "none" => Some(SourceMapMode::Off),
+            "inline" => Some(SourceMapMode::Inline),
+            "file" => Some(SourceMapMode::File),
+            _ => None
+        }
+    }
+}
+
+impl Default for SourceMapMode {
+    fn default() -> SourceMapMode { SourceMapMode::Off }
+}
+
+/// Marker recording that the JS written next was generated from the template source at `span`
+pub fn span_marker(span: Span) -> String {
+    format!("{}{},{}{}", MARKER_START, span.lo.0, span.hi.0, MARKER_END)
+}
+
+/// Remove span markers from generated JS, returning the JS and the byte offset of each marked span in it
+pub fn strip_span_markers(source: &str) -> (String, Vec<(usize, Span)>) {
+    let mut out = String::with_capacity(source.len());
+    let mut spans = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find(MARKER_START) {
+        out.push_str(&rest[..start]);
+        let marker = &rest[start + 1..];
+        let end = match marker.find(MARKER_END) {
+            Some(end) => end,
+            None => { rest = marker; continue; }
+        };
+
+        let mut positions = marker[..end].split(',').filter_map(|pos| pos.parse::<u32>().ok());
+        if let (Some(lo), Some(hi)) = (positions.next(), positions.next()) {
+            spans.push((out.len(), mk_sp(BytePos(lo), BytePos(hi))));
+        }
+        rest = &marker[end + 1..];
+    }
+    out.push_str(rest);
+
+    (out, spans)
+}
+
+/// Mapping from a position in the generated JS to a position in a source file (all zero based)
+#[derive(Clone, Debug)]
+struct Mapping {
+    generated_line: usize,
+    generated_column: usize,
+    source: usize,
+    source_line: usize,
+    source_column: usize
+}
+
+/// Version 3 source map for a generated JS file
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    file: String,
+    sources: Vec<String>,
+    sources_content: Vec<Option<String>>,
+    mappings: Vec<Mapping>
+}
+
+impl SourceMap {
+    pub fn new(file: &str) -> SourceMap {
+        SourceMap {
+            file: file.to_owned(),
+            sources: vec![],
+            sources_content: vec![],
+            mappings: vec![]
+        }
+    }
+
+    /// Map the spans found by `strip_span_markers` back to the template text
+    pub fn from_spans(codemap: &CodeMap, file: &str, generated: &str, spans: &[(usize, Span)]) -> SourceMap {
+        let mut source_map = SourceMap::new(file);
+        let offsets: Vec<usize> = spans.iter().map(|&(offset, _)| offset).collect();
+        let positions = generated_positions(generated, &offsets);
+
+        for (&(_, span), (line, column)) in spans.iter().zip(positions) {
+            if span.lo.0 == 0 && span.hi.0 == 0 {
+                continue;
+            }
+
+            let loc = codemap.lookup_char_pos(span.lo);
+            let content = loc.file.src.as_ref().map(|src| src.to_string());
+            let source = source_map.add_source(&loc.file.name, content);
+            source_map.mappings.push(Mapping {
+                generated_line: line,
+                generated_column: column,
+                source: source,
+                source_line: loc.line - 1,
+                source_column: loc.col.0
+            });
+        }
+
+        source_map
+    }
+
+    fn add_source(&mut self, name: &str, content: Option<String>) -> usize {
+        if let Some(idx) = self.sources.iter().position(|source| source == name) {
+            return idx;
+        }
+        self.sources.push(name.to_owned());
+        self.sources_content.push(content);
+        self.sources.len() - 1
+    }
+
+    /// The `mappings` field: segments of base64 VLQ fields, relative to the previous segment
+    fn encode_mappings(&self) -> String {
+        let mut out = String::new();
+        let (mut line, mut prev_column, mut prev_source, mut prev_line, mut prev_source_column) = (0, 0, 0, 0, 0);
+
+        for (idx, mapping) in self.mappings.iter().enumerate() {
+            if mapping.generated_line > line {
+                for _ in line..mapping.generated_line {
+                    out.push(';');
+                }
+                line = mapping.generated_line;
+                prev_column = 0;
+            } else if idx > 0 {
+                out.push(',');
+            }
+
+            encode_vlq(&mut out, mapping.generated_column as i64 - prev_column as i64);
+            encode_vlq(&mut out, mapping.source as i64 - prev_source as i64);
+            encode_vlq(&mut out, mapping.source_line as i64 - prev_line as i64);
+            encode_vlq(&mut out, mapping.source_column as i64 - prev_source_column as i64);
+
+            prev_column = mapping.generated_column;
+            prev_source = mapping.source;
+            prev_line = mapping.source_line;
+            prev_source_column = mapping.source_column;
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let sources: Vec<String> = self.sources.iter().map(|source| json_string(source)).collect();
+        let sources_content: Vec<String> = self.sources_content.iter().map(|content| match content {
+            &Some(ref content) => json_string(content),
+            &None => "null".to_owned()
+        }).collect();
+
+        format!("{{\"version\":3,\"file\":{},\"sources\":[{}],\"sourcesContent\":[{}],\"names\":[],\"mappings\":{}}}",
+            json_string(&self.file), sources.join(","), sources_content.join(","), json_string(&self.encode_mappings()))
+    }
+
+    /// Comment embedding the source map in the generated JS
+    pub fn to_inline_comment(&self) -> String {
+        format!("\n//# sourceMappingURL=data:application/json;charset=utf-8;base64,{}\n", base64_encode(self.to_json().as_bytes()))
+    }
+}
+
+/// Comment referencing a source map served separately
+pub fn url_comment(url: &str) -> String {
+    format!("\n//# sourceMappingURL={}\n", url)
+}
+
+/// Line and column (in UTF-16 code units, as JS counts them) of each byte offset, which must be in order
+fn generated_positions(generated: &str, offsets: &[usize]) -> Vec<(usize, usize)> {
+    let mut positions = Vec::with_capacity(offsets.len());
+    let mut offsets = offsets.iter().peekable();
+    let (mut line, mut column) = (0, 0);
+    let mut chars = generated.char_indices().peekable();
+
+    loop {
+        let pos = chars.peek().map(|&(pos, _)| pos).unwrap_or(generated.len());
+        while offsets.peek().map_or(false, |&&offset| offset <= pos) {
+            offsets.next();
+            positions.push((line, column));
+        }
+
+        match chars.next() {
+            Some((_, '\r')) if chars.peek().map(|&(_, c)| c) == Some('\n') => { column += 1; },
+            Some((_, '\n')) | Some((_, '\r')) => { line += 1; column = 0; },
+            Some((_, c)) => { column += c.len_utf16(); },
+            None => break
+        };
+    }
+
+    positions
+}
+
+fn encode_vlq(out: &mut String, value: i64) {
+    let mut vlq = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = vlq & 0b11111;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64_CHARS[digit as usize] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as usize) << 16) | ((b[1] as usize) << 8) | (b[2] as usize);
+        out.push(BASE64_CHARS[(n >> 18) & 63] as char);
+        out.push(BASE64_CHARS[(n >> 12) & 63] as char);
+        out.push(if chunk.len() > 1 { BASE64_CHARS[(n >> 6) & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { BASE64_CHARS[n & 63] as char } else { '=' });
+    }
+    out
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn test_source_map_encoding() {
+    let mut out = String::new();
+    for value in &[0, 1, -1, 16, 123] {
+        encode_vlq(&mut out, *value);
+    }
+    assert_eq!(out, "ACDgB2H");
+    assert_eq!(base64_encode(b"incrust"), "aW5jcnVzdA==");
+
+    let marked = format!("a{}b\r\n{}c", span_marker(mk_sp(BytePos(3), BytePos(5))), span_marker(mk_sp(BytePos(7), BytePos(9))));
+    let (generated, spans) = strip_span_markers(&marked);
+    assert_eq!(generated, "ab\r\nc");
+    let offsets: Vec<usize> = spans.iter().map(|&(offset, _)| offset).collect();
+    assert_eq!(offsets, vec![1, 4]);
+    assert_eq!(generated_positions(&generated, &offsets), vec![(0, 1), (1, 0)]);
+}
+
+#[test]
+fn test_span_markers_in_generated_js() {
+    use js_write::{JsFormatter, JsFormat, JsWriteFunctions, WriteJs};
+    use js_backend::INCREMENTAL_DOM;
+    use output_actions::OutputAction;
+
+    fn write_render(funcs: &mut JsWriteFunctions, output_actions: &[OutputAction]) {
+        funcs.function("render", vec!["state"], &|js| {
+            for output_action in output_actions {
+                output_action.write_js(js, &INCREMENTAL_DOM);
+            }
+        });
+    }
+
+    let output_actions = vec![
+        OutputAction::WriteOpen("p".to_owned(), vec![], mk_sp(BytePos(10), BytePos(30))),
+        OutputAction::Write("Counter".to_owned()),
+        OutputAction::WriteClose("p".to_owned(), mk_sp(BytePos(10), BytePos(30)))
+    ];
+    let mut marked = String::new();
+    write_render(&mut JsFormatter::new(&mut marked, JsFormat::Pretty), &output_actions);
+
+    let (generated, spans) = strip_span_markers(&marked);
+    assert_eq!(generated, "function render(state) {\n    IncrementalDOM.elementOpen(\"p\");\n    IncrementalDOM.text(\"Counter\");\n    IncrementalDOM.elementClose(\"p\");\n}");
+
+    // Each mapping starts at the statement generated from the span, not at the end of the line before it
+    let offsets: Vec<usize> = spans.iter().map(|&(offset, _)| offset).collect();
+    assert_eq!(generated_positions(&generated, &offsets), vec![(1, 4), (3, 4)]);
+}
@@ -0,0 +1,42 @@
+//! Helpers for unit tests which parse and check template source
+
+use syntax::codemap::DUMMY_SP;
+use syntax::ext::base::{DummyResolver, ExtCtxt};
+use syntax::ext::expand::ExpansionConfig;
+use syntax::parse::{ParseSess, new_parser_from_source_str};
+
+use nodes::template_node::{Template, TemplateNode};
+use nodes::view_node::View;
+use nodes::content_node::ContentNode;
+use nodes::element_node::Element;
+use nodes::template_node::parse::parse_template;
+use diagnostics::Diagnostic;
+
+
+/// Run `f` with an extension context outside of a compiler session
+pub fn with_ecx<F: FnOnce(&mut ExtCtxt)>(f: F) {
+    let sess = ParseSess::new();
+    let mut resolver = DummyResolver;
+    let mut ecx = ExtCtxt::new(&sess, vec![], ExpansionConfig::default("incrust_test".to_owned()), &mut resolver);
+    f(&mut ecx);
+}
+
+/// Parse the contents of a `template! main { ... }` invocation
+pub fn parse_template_source(ecx: &ExtCtxt, source: &str) -> (Template, Vec<Diagnostic>) {
+    let mut parser = new_parser_from_source_str(ecx.parse_sess, ecx.cfg(), "<template>".to_owned(), source.to_owned());
+    parse_template(ecx, &mut parser, DUMMY_SP, "main")
+}
+
+/// First view declared in the template
+pub fn first_view(template: &Template) -> &View {
+    template.nodes().iter()
+        .filter_map(|node| match node { &TemplateNode::ViewNode(_, ref view) => Some(view), _ => None })
+        .next().expect("template has no view")
+}
+
+/// First element at the top of the first view
+pub fn first_element(template: &Template) -> &Element {
+    first_view(template).nodes().iter()
+        .filter_map(|node| match node { &ContentNode::ElementNode(ref element) => Some(element), _ => None })
+        .next().expect("view has no element")
+}
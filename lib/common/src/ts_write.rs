@@ -0,0 +1,97 @@
+use std::fmt::Write;
+use syntax::ext::base::ExtCtxt;
+use types::ValueType;
+
+
+/// Request the object write itself out as TypeScript declarations (*.d.ts) for the generated JS
+pub trait WriteTsDeclarations {
+    fn write_ts_declarations<'cx>(&self, ecx: &'cx ExtCtxt, ts: &mut TsWrite);
+}
+
+pub trait TsWrite {
+    fn comment(&mut self, text: &str);
+    fn interface(&mut self, name: &str, members: &[(String, String)]);
+    fn type_alias(&mut self, name: &str, ty: &str);
+    fn declare_function(&mut self, func_name: &str, params: &[(String, String)], ret_ty: &str);
+}
+
+/// TypeScript type for a template value type
+pub fn ts_type(ty: &ValueType) -> String {
+    match ty {
+        &ValueType::String => "string".to_owned(),
+        &ValueType::Number => "number".to_owned(),
+        &ValueType::Bool => "boolean".to_owned(),
+        &ValueType::Model(ref name) => name.to_owned(),
+        &ValueType::Any | &ValueType::Unknown => "any".to_owned()
+    }
+}
+
+/// Object type literal, such as `{ type: "INCREMENT"; amount: number }`
+pub fn ts_object_type(members: &[(String, String)]) -> String {
+    let members: Vec<String> = members.iter()
+        .map(|&(ref name, ref ty)| format!("{}: {}", name, ty))
+        .collect();
+    format!("{{ {} }}", members.join("; "))
+}
+
+/// Type name prefix derived from an output name, e.g. `main` => `Main`
+pub fn ts_type_prefix(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new()
+            }
+        })
+        .collect()
+}
+
+impl<T: Write> TsWrite for T {
+    fn comment(&mut self, text: &str) {
+        write!(self, "// {}\n", text);
+    }
+
+    fn interface(&mut self, name: &str, members: &[(String, String)]) {
+        write!(self, "interface {} {{\n", name);
+        for &(ref member_name, ref ty) in members {
+            write!(self, "    {}: {};\n", member_name, ty);
+        }
+        write!(self, "}}\n");
+    }
+
+    fn type_alias(&mut self, name: &str, ty: &str) {
+        write!(self, "type {} = {};\n", name, ty);
+    }
+
+    fn declare_function(&mut self, func_name: &str, params: &[(String, String)], ret_ty: &str) {
+        let params: Vec<String> = params.iter()
+            .map(|&(ref name, ref ty)| format!("{}: {}", name, ty))
+            .collect();
+        write!(self, "declare function {}({}): {};\n", func_name, params.join(", "), ret_ty);
+    }
+}
+
+mod output_strings {
+    use super::WriteTsDeclarations;
+    use syntax::codemap::DUMMY_SP;
+    use syntax::ext::base::ExtCtxt;
+    use codegen::lang::Ts;
+    use codegen::output_string_writer::{WriteOutputStrings, OutputStringWrite};
+
+    impl<S: WriteTsDeclarations> WriteOutputStrings<Ts> for S {
+        fn write_output_strings<'s, 'cx>(&self, ecx: &'cx ExtCtxt, w: &'s mut OutputStringWrite<Ts>) {
+            debug!("Writing output strings for ts declarations");
+
+            let mut out = String::new();
+            self.write_ts_declarations(ecx, &mut out);
+            w.write_output_string(ecx, &out);
+        }
+    }
+}
+
+#[test]
+fn test_ts_type_prefix() {
+    assert_eq!(ts_type_prefix("main"), "Main");
+    assert_eq!(ts_type_prefix("side_bar"), "SideBar");
+}
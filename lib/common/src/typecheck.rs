@@ -0,0 +1,718 @@
+use std::collections::HashMap;
+
+use syntax::codemap::Span;
+use syntax::ext::base::ExtCtxt;
+
+use types::ValueType;
+use simple_expr::{SimpleExpr, SimpleExprToken};
+use nodes::template_node::{Template, TemplateNode};
+use nodes::view_node::View;
+use nodes::store_node::{Store, StoreNode};
+use nodes::content_node::ContentNode;
+use nodes::match_node::{MatchBlock, MatchPattern};
+use nodes::let_node::LetBlock;
+use nodes::element_node::{ElementAttribute, AttributeKind};
+use nodes::model_node::{Model, ModelField};
+use model::registry;
+use diagnostics::{Diagnostic, DiagnosticCode, closest_name};
+
+
+/// Names visible to an expression, mapped to the type of their value
+pub type Scope = HashMap<String, ValueType>;
+
+#[derive(Clone, Copy, Debug)]
+enum Binop {
+    Plus,
+    Minus
+}
+
+/// Checks view and store expressions against the declared models of a template.
+/// Errors are reported through the extension context, pointing at the expression span.
+pub struct TypeChecker<'cx, 'r: 'cx> {
+    ecx: &'cx ExtCtxt<'r>,
+    models: HashMap<String, Model>,
+    store_types: Vec<(String, ValueType)>,
+    report: bool,
+    errors: usize
+}
+
+impl<'cx, 'r> TypeChecker<'cx, 'r> {
+    pub fn new(ecx: &'cx ExtCtxt<'r>) -> TypeChecker<'cx, 'r> {
+        TypeChecker {
+            ecx: ecx,
+            models: HashMap::new(),
+            store_types: Vec::new(),
+            report: true,
+            errors: 0
+        }
+    }
+
+    /// Checker which only counts errors, used to resolve types after the template was checked
+    pub fn quiet(ecx: &'cx ExtCtxt<'r>) -> TypeChecker<'cx, 'r> {
+        TypeChecker { report: false, ..TypeChecker::new(ecx) }
+    }
+
+    pub fn errors(&self) -> usize { self.errors }
+
+    pub fn model(&self, name: &str) -> Option<&Model> {
+        self.models.get(name)
+    }
+
+    /// Declared or inferred state type of a store, once the template has been checked
+    pub fn store_type(&self, name: &str) -> Option<&ValueType> {
+        self.store_types.iter()
+            .find(|&&(ref store_name, _)| store_name == name)
+            .map(|&(_, ref ty)| ty)
+    }
+
+    fn error(&mut self, diagnostic: Diagnostic) {
+        if self.report {
+            diagnostic.emit(&self.ecx.parse_sess.span_diagnostic);
+        }
+        self.errors += 1;
+    }
+
+    pub fn check_template(&mut self, template: &Template) {
+        // Models derived with #[derive(IncrustModel)], which template declarations may override
+        for model in registry::registered_models() {
+            self.models.insert(model.name().to_owned(), model);
+        }
+
+        // Models first, so stores and views may refer to models declared after them
+        let mut declared: Vec<String> = Vec::new();
+        for node in template.nodes() {
+            if let &TemplateNode::ModelNode(ref name, ref model) = node {
+                if declared.contains(name) {
+                    let previous_span = self.models[name].span();
+                    self.error(Diagnostic::error(DiagnosticCode::DuplicateModel, model.span(), &format!("model `{}` is declared more than once", name))
+                        .span_note(previous_span, "previously declared here"));
+                    continue;
+                }
+                declared.push(name.to_owned());
+                self.models.insert(name.to_owned(), model.clone());
+            }
+        }
+
+        for node in template.nodes() {
+            if let &TemplateNode::ModelNode(_, ref model) = node {
+                self.check_model(model);
+            }
+        }
+
+        // Derived models are checked where stores use them, as the crate may derive others not used
+        // by templates. Their field types must also be derived before the template, or declared in it.
+        let mut checked = declared.clone();
+        for node in template.nodes() {
+            if let &TemplateNode::StoreNode(_, ref store) = node {
+                if let Some(type_name) = store.state_type() {
+                    self.check_derived_model(type_name, &mut checked);
+                }
+            }
+        }
+
+        for node in template.nodes() {
+            if let &TemplateNode::StoreNode(_, ref store) = node {
+                self.check_store(store);
+            }
+        }
+
+        // Each view has its own entry points, which must not collide
+        let mut views: Vec<&View> = Vec::new();
+        for node in template.nodes() {
+            if let &TemplateNode::ViewNode(_, ref view) = node {
+                if let Some(previous) = views.iter().find(|previous| previous.name() == view.name()) {
+                    self.error(Diagnostic::error(DiagnosticCode::DuplicateView, view.span(), &format!("view `{}` is defined more than once in template `{}`", view.name(), template.name()))
+                        .span_note(previous.span(), "previously defined here"));
+                    continue;
+                }
+                views.push(view);
+                self.check_view(view);
+            }
+        }
+    }
+
+    fn check_field_type(&mut self, field: &ModelField) {
+        if let &ValueType::Model(ref type_name) = field.ty() {
+            if !self.models.contains_key(type_name) {
+                let diagnostic = self.unknown_type(field.span(), type_name, &format!("field `{}`", field.name()));
+                self.error(diagnostic);
+            }
+        }
+    }
+
+    fn unknown_type(&self, span: Span, type_name: &str, target: &str) -> Diagnostic {
+        let diagnostic = Diagnostic::error(DiagnosticCode::UnknownType, span, &format!("unknown type `{}` for {}", type_name, target))
+            .note("#[derive(IncrustModel)] models are only visible to templates expanded after the derive, e.g. from a module declared before the template");
+
+        match closest_name(type_name, self.models.keys().map(|name| name.as_str())) {
+            Some(candidate) => diagnostic.help(&format!("a model with a similar name exists: `{}`", candidate)),
+            None => diagnostic.help("declare it with `model Name { ... }` in the template, or #[derive(IncrustModel)] on a struct")
+        }
+    }
+
+    fn check_model(&mut self, model: &Model) {
+        for field in model.fields() {
+            self.check_field_type(field);
+        }
+
+        for variant in model.variants() {
+            for ty in variant.values() {
+                if let &ValueType::Model(ref type_name) = ty {
+                    if !self.models.contains_key(type_name) {
+                        let diagnostic = self.unknown_type(variant.span(), type_name, &format!("variant `{}`", variant.name()));
+                        self.error(diagnostic);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check a derived model and the models its fields and variants refer to, each once
+    fn check_derived_model(&mut self, type_name: &str, checked: &mut Vec<String>) {
+        if checked.iter().any(|name| name == type_name) {
+            return;
+        }
+        checked.push(type_name.to_owned());
+
+        let model = match self.models.get(type_name) {
+            Some(model) => model.clone(),
+            None => { return; }
+        };
+        self.check_model(&model);
+
+        let field_types = model.fields().iter().map(|field| field.ty());
+        let value_types = model.variants().iter().flat_map(|variant| variant.values().iter());
+        for ty in field_types.chain(value_types) {
+            if let &ValueType::Model(ref type_name) = ty {
+                self.check_derived_model(type_name, checked);
+            }
+        }
+    }
+
+    /// Type of the store state, either declared or inferred from the default expression
+    fn store_state_type(&mut self, store: &Store) -> ValueType {
+        if let Some(type_name) = store.state_type() {
+            let ty = ValueType::from_type_name(type_name);
+            if let ValueType::Model(ref model_name) = ty {
+                if !self.models.contains_key(model_name) {
+                    let diagnostic = self.unknown_type(store.span(), model_name, &format!("store `{}`", store.name()));
+                    self.error(diagnostic);
+                    return ValueType::Unknown;
+                }
+            }
+            return ty;
+        }
+
+        for node in store.nodes() {
+            if let &StoreNode::DefaultExpr(ref simple_expr) = node {
+                return self.infer_simple_expr(simple_expr, &Scope::new());
+            }
+        }
+
+        ValueType::Unknown
+    }
+
+    fn check_reducer_result(&mut self, store: &Store, state_ty: &ValueType, simple_expr: &SimpleExpr, scope: &Scope, label: &str) {
+        let result_ty = self.infer_simple_expr(simple_expr, scope);
+
+        // Model values can't be constructed in template expressions yet, so only check primitive state
+        if let &ValueType::Model(_) = state_ty {
+            return;
+        }
+
+        if !result_ty.is_compatible(state_ty) {
+            self.error(Diagnostic::error(DiagnosticCode::ReducerMismatch, simple_expr.span(), &format!("mismatched types: {} for store `{}` returns {}, but the store holds {}",
+                label, store.name(), result_ty, state_ty))
+                .span_note(store.span(), &format!("store `{}` declared here", store.name())));
+        }
+    }
+
+    fn check_store(&mut self, store: &Store) {
+        let state_ty = self.store_state_type(store);
+        self.store_types.push((store.name().to_owned(), state_ty.clone()));
+
+        for node in store.nodes() {
+            match node {
+                &StoreNode::DefaultExpr(ref simple_expr) => {
+                    let mut scope = Scope::new();
+                    scope.insert(store.name().to_owned(), state_ty.clone());
+                    self.check_reducer_result(store, &state_ty, simple_expr, &scope, "default");
+                },
+
+                &StoreNode::ActionExpr(ref act, ref params, ref simple_expr) => {
+                    for param in params {
+                        self.check_field_type(param);
+                    }
+
+                    // The action payload is visible as `action`, with the implicit `type` member
+                    let payload_name = format!("action {}", act);
+                    let mut fields = vec![ModelField::new(simple_expr.span(), "type", ValueType::String)];
+                    fields.extend(params.iter().cloned());
+                    self.models.insert(payload_name.to_owned(), Model::new(simple_expr.span(), &payload_name, fields));
+
+                    let mut scope = Scope::new();
+                    scope.insert(store.name().to_owned(), state_ty.clone());
+                    scope.insert("action".to_owned(), ValueType::Model(payload_name.to_owned()));
+                    self.check_reducer_result(store, &state_ty, simple_expr, &scope, &format!("action {}", act));
+
+                    self.models.remove(&payload_name);
+                },
+
+                // Bound paths are checked in the views
+                &StoreNode::BindAction(..) => {}
+            }
+        }
+    }
+
+    fn view_scope(&self) -> Scope {
+        self.store_types.iter().cloned().collect()
+    }
+
+    fn check_view(&mut self, view: &View) {
+        let scope = self.view_scope();
+        self.check_contents(view.nodes(), &scope);
+    }
+
+    fn check_contents(&mut self, nodes: &[ContentNode], scope: &Scope) {
+        for node in nodes {
+            match node {
+                &ContentNode::ElementNode(ref element) => {
+                    for attribute in element.attributes() {
+                        if let &ElementAttribute::Bind(ref property, ref path) = attribute {
+                            self.check_bind(property, path, scope);
+                            continue;
+                        }
+                        if let &ElementAttribute::Value(ref name, ref condition) = attribute {
+                            // `disabled=true` and `disabled=false` are literals rather than names
+                            let literal = condition.reference_path().map_or(false, |path| path == "true" || path == "false");
+                            if literal && AttributeKind::of(name).is_boolean() {
+                                continue;
+                            }
+                        }
+                        for simple_expr in attribute.exprs() {
+                            self.infer_simple_expr(simple_expr, scope);
+                        }
+                    }
+                    self.check_contents(element.nodes(), scope);
+                },
+
+                &ContentNode::ExprNode(ref simple_expr) => {
+                    self.infer_simple_expr(simple_expr, scope);
+                },
+
+                &ContentNode::MatchNode(ref block) => {
+                    self.check_match(block, scope);
+                },
+
+                &ContentNode::LetNode(ref block) => {
+                    self.check_let(block, scope);
+                },
+
+                &ContentNode::LiteralNode(_) |
+                &ContentNode::FlushNode(_) => {}
+            }
+        }
+    }
+
+    /// The value matched on must be an enum model, each arm must name one of its variants with
+    /// a name for each value, and all variants must be covered unless there is a `_` arm.
+    /// Arms are checked with the bound names in scope.
+    fn check_match(&mut self, block: &MatchBlock, scope: &Scope) {
+        let ty = self.infer_simple_expr(block.expr(), scope);
+        let model = match ty {
+            ValueType::Model(ref model_name) => self.models.get(model_name).cloned(),
+            _ => None
+        };
+
+        // Variants of `Any` or unknown values can't be checked
+        let enum_model = match model {
+            Some(ref model) if model.is_enum() => Some(model.clone()),
+            _ if ty == ValueType::Any || ty == ValueType::Unknown => None,
+            _ => {
+                self.error(Diagnostic::error(DiagnosticCode::NotAnEnum, block.expr().span(), &format!("cannot match on `{}`, which has type {} rather than an enum", block.path(), ty))
+                    .help("declare the type as an enum, e.g. `model Load { Loading, Loaded(Data) }` or #[derive(IncrustModel)] on an enum"));
+                None
+            }
+        };
+
+        let mut covered: Vec<String> = Vec::new();
+        let mut has_wildcard = false;
+
+        for arm in block.arms() {
+            let mut arm_scope = scope.clone();
+
+            if let &MatchPattern::Variant(ref variant_name, ref bindings) = arm.pattern() {
+                let value_types = match enum_model {
+                    Some(ref model) => match model.variant(variant_name) {
+                        Some(variant) => {
+                            if bindings.len() != variant.values().len() {
+                                self.error(Diagnostic::error(DiagnosticCode::PatternMismatch, arm.span(), &format!("variant `{}::{}` has {} value(s), but the pattern binds {}",
+                                    model.name(), variant_name, variant.values().len(), bindings.len()))
+                                    .span_note(variant.span(), &format!("variant `{}` declared here", variant_name)));
+                            }
+                            Some(variant.values().to_vec())
+                        },
+                        None => {
+                            let variant_names: Vec<&str> = model.variants().iter().map(|variant| variant.name()).collect();
+                            let diagnostic = Diagnostic::error(DiagnosticCode::UnknownVariant, arm.span(), &format!("no variant `{}` on enum `{}`", variant_name, model.name()))
+                                .note(&format!("variants: {}", variant_names.join(", ")));
+                            let diagnostic = match closest_name(variant_name, variant_names.iter().map(|name| *name)) {
+                                Some(candidate) => diagnostic.help(&format!("did you mean `{}`?", candidate)),
+                                None => diagnostic
+                            };
+                            self.error(diagnostic);
+                            None
+                        }
+                    },
+                    None => None
+                };
+
+                for (idx, binding) in bindings.iter().enumerate().filter(|&(_, binding)| binding != "_") {
+                    let binding_ty = match value_types {
+                        Some(ref value_types) => value_types.get(idx).cloned().unwrap_or(ValueType::Unknown),
+                        None if ty == ValueType::Any => ValueType::Any,
+                        None => ValueType::Unknown
+                    };
+                    arm_scope.insert(binding.to_owned(), binding_ty);
+                }
+                covered.push(variant_name.to_owned());
+            } else {
+                has_wildcard = true;
+            }
+
+            self.check_contents(arm.nodes(), &arm_scope);
+        }
+
+        if let Some(ref model) = enum_model {
+            let missing: Vec<&str> = model.variants().iter()
+                .map(|variant| variant.name())
+                .filter(|name| !covered.iter().any(|covered| covered == name))
+                .collect();
+
+            if !has_wildcard && !missing.is_empty() {
+                let missing: Vec<String> = missing.iter().map(|name| format!("`{}`", name)).collect();
+                self.error(Diagnostic::error(DiagnosticCode::NonExhaustiveMatch, block.span(), &format!("non-exhaustive match on `{}`: {} not covered", block.path(), missing.join(", ")))
+                    .span_note(model.span(), &format!("enum `{}` declared here", model.name()))
+                    .help("add an arm for each variant, or `_ => [ ... ]` for the rest"));
+            }
+        }
+    }
+
+    /// The contents of a let block are checked with the name in scope, having the type of its value
+    fn check_let(&mut self, block: &LetBlock, scope: &Scope) {
+        let ty = self.infer_simple_expr(block.expr(), scope);
+        let mut let_scope = scope.clone();
+        let_scope.insert(block.name().to_owned(), ty);
+        self.check_contents(block.nodes(), &let_scope);
+    }
+
+    /// Inputs give text, which the generated action stores in the bound field
+    fn check_bind(&mut self, property: &str, path: &SimpleExpr, scope: &Scope) {
+        let ty = self.infer_simple_expr(path, scope);
+        if !ty.is_compatible(&ValueType::String) {
+            self.error(Diagnostic::error(DiagnosticCode::BindMismatch, path.span(), &format!("mismatched types: `bind:{}` sets a String, but `{}` holds {}",
+                property, path.reference_path().unwrap_or(""), ty)));
+        }
+    }
+
+    /// Infer the type of an expression, reporting any errors found along the way
+    pub fn infer_simple_expr(&mut self, simple_expr: &SimpleExpr, scope: &Scope) -> ValueType {
+        let tokens = simple_expr.tokens();
+        let mut pos = 0;
+        let ty = self.infer_expr(tokens, &mut pos, scope, simple_expr.span());
+
+        if pos < tokens.len() {
+            self.error(Diagnostic::error(DiagnosticCode::MalformedExpression, simple_expr.span(), &format!("unexpected token in expression: {:?}", &tokens[pos])));
+        }
+        ty
+    }
+
+    fn infer_expr(&mut self, tokens: &[SimpleExprToken], pos: &mut usize, scope: &Scope, span: Span) -> ValueType {
+        let mut ty = self.infer_operand(tokens, pos, scope, span);
+
+        while *pos < tokens.len() {
+            let op = match tokens[*pos] {
+                SimpleExprToken::BinopPlus => Binop::Plus,
+                SimpleExprToken::BinopMinus => Binop::Minus,
+                _ => break
+            };
+            *pos += 1;
+
+            let rhs = self.infer_operand(tokens, pos, scope, span);
+            ty = self.binop_type(op, &ty, &rhs, span);
+        }
+
+        ty
+    }
+
+    fn infer_operand(&mut self, tokens: &[SimpleExprToken], pos: &mut usize, scope: &Scope, span: Span) -> ValueType {
+        if *pos >= tokens.len() {
+            return ValueType::Unknown;
+        }
+
+        let token = tokens[*pos].clone();
+        *pos += 1;
+
+        match token {
+            SimpleExprToken::VarReference(ref var_name) => self.resolve_var_reference(var_name, scope, span),
+            SimpleExprToken::DynamicReference(_) => ValueType::Any,
+            SimpleExprToken::LitString(_) => ValueType::String,
+            SimpleExprToken::LitNumber(_) => ValueType::Number,
+
+            SimpleExprToken::OpenParen => {
+                let ty = self.infer_expr(tokens, pos, scope, span);
+                match tokens.get(*pos) {
+                    Some(&SimpleExprToken::CloseParen) => { *pos += 1; },
+                    _ => { self.error(Diagnostic::error(DiagnosticCode::MalformedExpression, span, "unclosed parenthesis in expression")); }
+                };
+                ty
+            },
+
+            _ => {
+                self.error(Diagnostic::error(DiagnosticCode::MalformedExpression, span, &format!("expected a value in expression, found {:?}", &token)));
+                ValueType::Unknown
+            }
+        }
+    }
+
+    fn binop_type(&mut self, op: Binop, lhs: &ValueType, rhs: &ValueType, span: Span) -> ValueType {
+        match (op, lhs, rhs) {
+            (_, &ValueType::Unknown, _) | (_, _, &ValueType::Unknown) => ValueType::Unknown,
+            (_, &ValueType::Any, _) | (_, _, &ValueType::Any) => ValueType::Any,
+            (_, &ValueType::Number, &ValueType::Number) => ValueType::Number,
+            (Binop::Plus, &ValueType::String, &ValueType::String) => ValueType::String,
+            _ => {
+                let op_str = match op { Binop::Plus => "+", Binop::Minus => "-" };
+                let diagnostic = Diagnostic::error(DiagnosticCode::OperatorMismatch, span, &format!("mismatched types: cannot apply `{}` to {} and {}", op_str, lhs, rhs));
+                let diagnostic = match (op, lhs, rhs) {
+                    (Binop::Plus, &ValueType::String, _) | (Binop::Plus, _, &ValueType::String) => diagnostic.note("`+` concatenates two strings, or adds two numbers"),
+                    _ => diagnostic
+                };
+                self.error(diagnostic);
+                ValueType::Unknown
+            }
+        }
+    }
+
+    fn unknown_field(&self, span: Span, model_name: &str, field_name: &str) -> Diagnostic {
+        let field_names: Vec<&str> = self.models.get(model_name)
+            .map(|model| model.fields().iter().map(|field| field.name()).collect())
+            .unwrap_or(vec![]);
+
+        // Action payloads are checked as models named `action {ACTION}`
+        let diagnostic = if model_name.starts_with("action ") {
+            Diagnostic::error(DiagnosticCode::UnknownActionPayload, span, &format!("{} has no payload field `{}`", model_name, field_name))
+                .note(&format!("declared payload: {}", field_names.join(", ")))
+        } else {
+            Diagnostic::error(DiagnosticCode::UnknownField, span, &format!("no field `{}` on type `{}`", field_name, model_name))
+                .note(&format!("available fields: {}", field_names.join(", ")))
+        };
+
+        match closest_name(field_name, field_names.iter().map(|name| *name)) {
+            Some(candidate) => diagnostic.help(&format!("did you mean `{}`?", candidate)),
+            None => diagnostic
+        }
+    }
+
+    /// Resolve a dotted path such as `person.first_name` against the scope and declared models
+    pub fn resolve_var_reference(&mut self, var_name: &str, scope: &Scope, span: Span) -> ValueType {
+        let mut parts = var_name.split('.');
+        let root = parts.next().unwrap_or("");
+
+        let mut ty = match scope.get(root) {
+            Some(ty) => ty.clone(),
+            None => {
+                let diagnostic = Diagnostic::error(DiagnosticCode::UnresolvedName, span, &format!("unresolved name `{}`", root));
+                let diagnostic = match closest_name(root, scope.keys().map(|name| name.as_str())) {
+                    Some(candidate) => diagnostic.help(&format!("did you mean `{}`?", candidate)),
+                    None => {
+                        let mut names: Vec<&str> = scope.keys().map(|name| name.as_str()).collect();
+                        names.sort();
+                        diagnostic.note(&format!("names in scope: {}", names.join(", ")))
+                    }
+                };
+                self.error(diagnostic);
+                return ValueType::Unknown;
+            }
+        };
+
+        for field_name in parts {
+            let field_ty = match ty {
+                ValueType::Unknown => ValueType::Unknown,
+                ValueType::Any => ValueType::Any,
+
+                ValueType::Model(ref model_name) => {
+                    let member_ty = self.models.get(model_name)
+                        .map(|model| model.field(field_name).map(|field| field.ty().clone()));
+
+                    match member_ty {
+                        Some(Some(field_ty)) => field_ty,
+                        Some(None) => {
+                            let diagnostic = self.unknown_field(span, model_name, field_name);
+                            self.error(diagnostic);
+                            return ValueType::Unknown;
+                        },
+                        None => ValueType::Unknown
+                    }
+                },
+
+                _ => {
+                    self.error(Diagnostic::error(DiagnosticCode::NotAModel, span, &format!("cannot access field `{}` of `{}`, which is not a model (has type {})", field_name, var_name, ty))
+                        .help("declare the store state type as a model, or as `Any` to disable checks"));
+                    return ValueType::Unknown;
+                }
+            };
+            ty = field_ty;
+        }
+
+        ty
+    }
+}
+
+/// Check all expressions in the template, returning the number of errors reported
+pub fn check_template<'cx, 'r>(ecx: &'cx ExtCtxt<'r>, template: &Template) -> usize {
+    let mut checker = TypeChecker::new(ecx);
+    checker.check_template(template);
+    checker.errors()
+}
+
+#[cfg(test)]
+fn first_store(template: &Template) -> &Store {
+    template.nodes().iter()
+        .filter_map(|node| match node { &TemplateNode::StoreNode(_, ref store) => Some(store), _ => None })
+        .next().unwrap()
+}
+
+#[test]
+fn test_resolve_var_reference() {
+    use syntax::codemap::DUMMY_SP;
+    use testing::with_ecx;
+
+    with_ecx(|ecx| {
+        let mut checker = TypeChecker::quiet(ecx);
+        checker.models.insert("Person".to_owned(), Model::new(DUMMY_SP, "Person", vec![ModelField::new(DUMMY_SP, "first_name", ValueType::String)]));
+        let mut scope = Scope::new();
+        scope.insert("person".to_owned(), ValueType::Model("Person".to_owned()));
+        scope.insert("counter".to_owned(), ValueType::Number);
+
+        assert_eq!(checker.resolve_var_reference("person.first_name", &scope, DUMMY_SP), ValueType::String);
+        assert_eq!(checker.resolve_var_reference("counter", &scope, DUMMY_SP), ValueType::Number);
+        assert_eq!(checker.errors(), 0);
+
+        assert_eq!(checker.resolve_var_reference("person.last_name", &scope, DUMMY_SP), ValueType::Unknown);
+        assert_eq!(checker.resolve_var_reference("persn.first_name", &scope, DUMMY_SP), ValueType::Unknown);
+        assert_eq!(checker.resolve_var_reference("counter.value", &scope, DUMMY_SP), ValueType::Unknown);
+        assert_eq!(checker.errors(), 3);
+    });
+}
+
+#[test]
+fn test_binop_type() {
+    use syntax::codemap::DUMMY_SP;
+    use testing::with_ecx;
+
+    with_ecx(|ecx| {
+        let mut checker = TypeChecker::quiet(ecx);
+        assert_eq!(checker.binop_type(Binop::Plus, &ValueType::Number, &ValueType::Number, DUMMY_SP), ValueType::Number);
+        assert_eq!(checker.binop_type(Binop::Plus, &ValueType::String, &ValueType::String, DUMMY_SP), ValueType::String);
+        assert_eq!(checker.binop_type(Binop::Minus, &ValueType::Any, &ValueType::Number, DUMMY_SP), ValueType::Any);
+        assert_eq!(checker.errors(), 0);
+
+        assert_eq!(checker.binop_type(Binop::Minus, &ValueType::String, &ValueType::Number, DUMMY_SP), ValueType::Unknown);
+        assert_eq!(checker.binop_type(Binop::Plus, &ValueType::String, &ValueType::Number, DUMMY_SP), ValueType::Unknown);
+        assert_eq!(checker.errors(), 2);
+    });
+}
+
+#[test]
+fn test_check_reducer_result() {
+    use syntax::codemap::DUMMY_SP;
+    use simple_expr::parse::parse_interpolated_expr;
+    use testing::{with_ecx, parse_template_source};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, "store counter { default => (0) }");
+        assert!(diagnostics.is_empty());
+        let store = first_store(&template);
+
+        let mut diagnostics = Vec::new();
+        let increment = parse_interpolated_expr(ecx, "counter + 1", DUMMY_SP, &mut diagnostics).unwrap();
+        let text = parse_interpolated_expr(ecx, "\"a\"", DUMMY_SP, &mut diagnostics).unwrap();
+        let mut scope = Scope::new();
+        scope.insert("counter".to_owned(), ValueType::Number);
+
+        let mut checker = TypeChecker::quiet(ecx);
+        checker.check_reducer_result(store, &ValueType::Number, &increment, &scope, "action INCREMENT");
+        assert_eq!(checker.errors(), 0);
+
+        checker.check_reducer_result(store, &ValueType::Number, &text, &scope, "action RESET");
+        assert_eq!(checker.errors(), 1);
+
+        // Model state isn't constructed in expressions, so it is not checked
+        checker.check_reducer_result(store, &ValueType::Model("Person".to_owned()), &text, &scope, "action RESET");
+        assert_eq!(checker.errors(), 1);
+    });
+}
+
+#[test]
+fn test_duplicate_views() {
+    use testing::{with_ecx, parse_template_source};
+
+    with_ecx(|ecx| {
+        let (template, diagnostics) = parse_template_source(ecx, r#"view root [ p [ "first" ] ] view other [ ] view root [ p [ "second" ] ]"#);
+        assert!(diagnostics.is_empty());
+
+        let mut checker = TypeChecker::quiet(ecx);
+        checker.check_template(&template);
+        assert_eq!(checker.errors(), 1);
+    });
+}
+
+#[test]
+fn test_match_exhaustiveness() {
+    use testing::{with_ecx, parse_template_source};
+
+    fn match_errors(ecx: &ExtCtxt, arms: &str) -> usize {
+        let source = format!("model Load {{ Loading, Loaded(String), Failed(String) }} store load: Load {{ }} view root [ match load [ {} ] ]", arms);
+        let (template, diagnostics) = parse_template_source(ecx, &source);
+        assert!(diagnostics.is_empty());
+
+        let mut checker = TypeChecker::quiet(ecx);
+        checker.check_template(&template);
+        checker.errors()
+    }
+
+    with_ecx(|ecx| {
+        assert_eq!(match_errors(ecx, "Loading => [ ], Loaded(title) => [ title ], Failed(error) => [ error ]"), 0);
+        assert_eq!(match_errors(ecx, "Loading => [ ], _ => [ ]"), 0);
+
+        // `Failed` is not covered
+        assert_eq!(match_errors(ecx, "Loading => [ ], Loaded(title) => [ title ]"), 1);
+        // Unknown variant, and a pattern binding the wrong number of values
+        assert_eq!(match_errors(ecx, "Loadin => [ ], _ => [ ]"), 1);
+        assert_eq!(match_errors(ecx, "Loading => [ ], Loaded(title, extra) => [ ], _ => [ ]"), 1);
+    });
+}
+
+#[test]
+fn test_unknown_model_types() {
+    use syntax::codemap::DUMMY_SP;
+    use testing::{with_ecx, parse_template_source};
+
+    fn type_errors(ecx: &ExtCtxt, source: &str) -> usize {
+        let (template, diagnostics) = parse_template_source(ecx, source);
+        assert!(diagnostics.is_empty());
+        let mut checker = TypeChecker::quiet(ecx);
+        checker.check_template(&template);
+        checker.errors()
+    }
+
+    // As if derived before the template, with a field whose model was derived after it
+    registry::register_model(Model::new(DUMMY_SP, "Account", vec![ModelField::new(DUMMY_SP, "owner", ValueType::Model("Owner".to_owned()))]));
+
+    with_ecx(|ecx| {
+        assert_eq!(type_errors(ecx, "store account: Account { }"), 1);
+        assert_eq!(type_errors(ecx, "model Owner { name: String } store account: Account { }"), 0);
+        assert_eq!(type_errors(ecx, "store person: Persn { }"), 1);
+    });
+}
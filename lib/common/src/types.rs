@@ -0,0 +1,55 @@
+use std::fmt;
+
+
+/// Type of a value reachable from template expressions.
+/// Only values backed by a declared model (struct) may be accessed by member.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    String,
+    Number,
+    Bool,
+    Model(String),
+    /// Declared `Any`: member access is passed through unchecked, and rendered from a dynamic value
+    Any,
+    /// The type could not be determined, checks involving it are skipped
+    Unknown
+}
+
+impl ValueType {
+    /// Map a Rust type name (as written in a model or action declaration) to a template type
+    pub fn from_type_name(type_name: &str) -> ValueType {
+        match type_name {
+            "String" | "str" => ValueType::String,
+            "bool" => ValueType::Bool,
+            "Any" => ValueType::Any,
+            "i8" | "i16" | "i32" | "i64" | "isize" |
+            "u8" | "u16" | "u32" | "u64" | "usize" |
+            "f32" | "f64" => ValueType::Number,
+            // Generic containers can't be checked yet
+            _ if type_name.contains('<') => ValueType::Unknown,
+            _ => ValueType::Model(type_name.to_owned())
+        }
+    }
+
+    /// Returns true if a value of this type may be used where `other` is expected
+    pub fn is_compatible(&self, other: &ValueType) -> bool {
+        match (self, other) {
+            (&ValueType::Unknown, _) | (_, &ValueType::Unknown) => true,
+            (&ValueType::Any, _) | (_, &ValueType::Any) => true,
+            _ => self == other
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ValueType::String => write!(f, "String"),
+            &ValueType::Number => write!(f, "number"),
+            &ValueType::Bool => write!(f, "bool"),
+            &ValueType::Model(ref name) => write!(f, "{}", name),
+            &ValueType::Any => write!(f, "Any"),
+            &ValueType::Unknown => write!(f, "{{unknown}}")
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use model::{ENUM_TAG, ENUM_VALUES};
+
+
+/// Dynamic (JSON-like) value, used to render state declared with the `Any` type on the server.
+/// Member access on such state is not checked at compile time, and is resolved by path when rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>)
+}
+
+impl Value {
+    /// Member of an object, or element of an array given a numeric key
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            &Value::Object(ref members) => members.get(key),
+            &Value::Array(ref elements) => key.parse::<usize>().ok().and_then(|idx| elements.get(idx)),
+            _ => None
+        }
+    }
+
+    /// Resolve a dotted path such as `person.address.city`
+    pub fn lookup(&self, path: &str) -> Option<&Value> {
+        let mut value = self;
+        for key in path.split('.') {
+            value = match value.get(key) {
+                Some(member) => member,
+                None => { return None; }
+            };
+        }
+        Some(value)
+    }
+
+    /// Serialized enum variant: `{"tag": "Loaded", "values": [...]}`
+    pub fn variant_of(name: &str, values: Vec<Value>) -> Value {
+        let mut members = BTreeMap::new();
+        members.insert(ENUM_TAG.to_owned(), Value::String(name.to_owned()));
+        members.insert(ENUM_VALUES.to_owned(), Value::Array(values));
+        Value::Object(members)
+    }
+
+    /// Variant name of a serialized enum
+    pub fn variant(&self) -> Option<&str> {
+        match self.get(ENUM_TAG) {
+            Some(&Value::String(ref name)) => Some(name),
+            _ => None
+        }
+    }
+
+    /// Whether the value counts as true in a condition, as in JS: not null, false, zero or empty text
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            &Value::Null => false,
+            &Value::Bool(b) => b,
+            &Value::Number(n) => n != 0.0 && !n.is_nan(),
+            &Value::String(ref s) => !s.is_empty(),
+            &Value::Array(_) |
+            &Value::Object(_) => true
+        }
+    }
+
+    /// Number the value converts to in arithmetic, as in JS: text is parsed, and anything else is NaN
+    pub fn to_number(&self) -> f64 {
+        match self {
+            &Value::Null => 0.0,
+            &Value::Bool(b) => if b { 1.0 } else { 0.0 },
+            &Value::Number(n) => n,
+            &Value::String(ref s) if s.trim().is_empty() => 0.0,
+            &Value::String(ref s) => s.trim().parse().unwrap_or(::std::f64::NAN),
+            &Value::Array(_) |
+            &Value::Object(_) => ::std::f64::NAN
+        }
+    }
+
+    /// `+` as in JS: adds numbers, and concatenates the text of the two values if either isn't one
+    pub fn plus(&self, other: &Value) -> Value {
+        match (self, other) {
+            (&Value::String(_), _) | (_, &Value::String(_)) |
+            (&Value::Array(_), _) | (_, &Value::Array(_)) |
+            (&Value::Object(_), _) | (_, &Value::Object(_)) => Value::String(format!("{}{}", self, other)),
+            _ => Value::Number(self.to_number() + other.to_number())
+        }
+    }
+
+    /// `-` as in JS, which always subtracts numbers
+    pub fn minus(&self, other: &Value) -> Value {
+        Value::Number(self.to_number() - other.to_number())
+    }
+
+    /// Serialize the value as JSON, e.g. to hydrate the client side store. The output is safe to
+    /// inline in a `<script>`: `<` is escaped, and numbers JSON can't represent are written as `null`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out).unwrap();
+        out
+    }
+
+    fn write_json<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        match self {
+            &Value::Null => write!(w, "null"),
+            &Value::Bool(b) => write!(w, "{}", b),
+            &Value::Number(n) if !n.is_finite() => write!(w, "null"),
+            &Value::Number(n) => write!(w, "{}", n),
+            &Value::String(ref s) => write_json_string(w, s),
+
+            &Value::Array(ref elements) => {
+                try!(write!(w, "["));
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 { try!(write!(w, ",")); }
+                    try!(element.write_json(w));
+                }
+                write!(w, "]")
+            },
+
+            &Value::Object(ref members) => {
+                try!(write!(w, "{{"));
+                for (idx, (key, member)) in members.iter().enumerate() {
+                    if idx > 0 { try!(write!(w, ",")); }
+                    try!(write_json_string(w, key));
+                    try!(write!(w, ":"));
+                    try!(member.write_json(w));
+                }
+                write!(w, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string<W: fmt::Write>(w: &mut W, s: &str) -> fmt::Result {
+    try!(write!(w, "\""));
+    for c in s.chars() {
+        match c {
+            '"' => try!(write!(w, "\\\"")),
+            '\\' => try!(write!(w, "\\\\")),
+            '\n' => try!(write!(w, "\\n")),
+            '\r' => try!(write!(w, "\\r")),
+            '\t' => try!(write!(w, "\\t")),
+            // Would close an inline script at `</script>`, or start a comment at `<!--`
+            '<' => try!(write!(w, "\\u003c")),
+            // Allowed in JSON strings, but line terminators in older JS engines
+            '\u{2028}' | '\u{2029}' => try!(write!(w, "\\u{:04x}", c as u32)),
+            c if (c as u32) < 0x20 => try!(write!(w, "\\u{:04x}", c as u32)),
+            c => try!(write!(w, "{}", c))
+        }
+    }
+    write!(w, "\"")
+}
+
+/// Text content of the value, unescaped; rendered views write it with `render::write_html_text`
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Value::Null => Ok(()),
+            &Value::Bool(b) => write!(f, "{}", b),
+            &Value::Number(n) => write!(f, "{}", n),
+            &Value::String(ref s) => write!(f, "{}", s),
+            _ => self.write_json(f)
+        }
+    }
+}
+
+impl<'s> From<&'s str> for Value {
+    fn from(s: &'s str) -> Value { Value::String(s.to_owned()) }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value { Value::String(s) }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value { Value::Bool(b) }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value { Value::Number(n as f64) }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value { Value::Number(n) }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Value { Value::Array(elements) }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(members: BTreeMap<String, Value>) -> Value { Value::Object(members) }
+}
+
+#[test]
+fn test_value_lookup() {
+    let mut person = BTreeMap::new();
+    person.insert("first_name".to_owned(), Value::from("Ada"));
+    let mut state = BTreeMap::new();
+    state.insert("person".to_owned(), Value::from(person));
+    let state = Value::from(state);
+
+    assert_eq!(state.lookup("person.first_name"), Some(&Value::from("Ada")));
+    assert_eq!(state.lookup("person.last_name"), None);
+    assert_eq!(state.to_json(), "{\"person\":{\"first_name\":\"Ada\"}}");
+
+    let loaded = Value::variant_of("Loaded", vec![Value::from("Ada")]);
+    assert_eq!(loaded.variant(), Some("Loaded"));
+    assert_eq!(loaded.lookup("values.0"), Some(&Value::from("Ada")));
+    assert_eq!(state.variant(), None);
+}
+
+#[test]
+fn test_value_to_json() {
+    assert_eq!(Value::from(vec![Value::Number(1.5), Value::Number(::std::f64::NAN), Value::Number(::std::f64::INFINITY)]).to_json(), "[1.5,null,null]");
+    assert_eq!(Value::from("</script><script>alert(1)").to_json(), "\"\\u003c/script>\\u003cscript>alert(1)\"");
+}
+
+#[test]
+fn test_value_operators() {
+    assert_eq!(Value::from(5i64).plus(&Value::from(1i64)), Value::Number(6.0));
+    assert_eq!(Value::from("Hello, ").plus(&Value::from("Ada")), Value::from("Hello, Ada"));
+    assert_eq!(Value::from("Count: ").plus(&Value::from(2i64)), Value::from("Count: 2"));
+    assert_eq!(Value::Null.plus(&Value::from(1i64)), Value::Number(1.0));
+    assert_eq!(Value::from("5").minus(&Value::from(1i64)), Value::Number(4.0));
+    assert!(Value::from("Ada").minus(&Value::from(1i64)).to_number().is_nan());
+}
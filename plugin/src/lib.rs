@@ -14,17 +14,81 @@ extern crate incrust_common;
 
 use rustc_plugin::Registry;
 
-use syntax::ext::base::{NormalTT, IdentTT};
+use syntax::ext::base::{NormalTT, IdentTT, MultiDecorator};
 use syntax::parse::token;
 
+use incrust_common::config::{Config, JsModuleFormat};
+use incrust_common::runtime::StoreRuntime;
+use incrust_common::js_backend::JsBackendKind;
+use incrust_common::source_map::SourceMapMode;
+use incrust_common::js_write::JsFormat;
+
 mod template_syntax;
+mod model_derive;
+
+
+/// Read the options given as plugin arguments, e.g. `#![plugin(incrust_plugin(store_runtime = "builtin", js_backend = "dom", js_module = "es"))]`
+fn plugin_config(reg: &Registry) -> Config {
+    let mut config = Config::default();
+
+    for arg in reg.args() {
+        let name = arg.name().map(|name| name.to_string()).unwrap_or_default();
+        let value = arg.value_str().map(|value| value.to_string());
 
+        match (name.as_ref(), value) {
+            ("store_runtime", Some(ref value)) => {
+                match StoreRuntime::from_name(value) {
+                    Some(store_runtime) => { config.store_runtime = store_runtime; },
+                    None => { reg.sess.span_err(arg.span, &format!("unknown store runtime `{}`, expected `redux` or `builtin`", value)); }
+                }
+            },
+            ("js_backend", Some(ref value)) => {
+                match JsBackendKind::from_name(value) {
+                    Some(js_backend) => { config.js_backend = js_backend; },
+                    None => { reg.sess.span_err(arg.span, &format!("unknown JS backend `{}`, expected `incremental_dom`, `dom`, `vdom` or `string`", value)); }
+                }
+            },
+            ("js_module", Some(ref value)) => {
+                match JsModuleFormat::from_name(value) {
+                    Some(js_module) => { config.js_module = js_module; },
+                    None => { reg.sess.span_err(arg.span, &format!("unknown JS module format `{}`, expected `global` or `es`", value)); }
+                }
+            },
+            ("runtime_module", Some(ref value)) => {
+                config.runtime_module = value.to_owned();
+            },
+            ("js_format", Some(ref value)) => {
+                match JsFormat::from_name(value) {
+                    Some(js_format) => { config.js_format = js_format; },
+                    None => { reg.sess.span_err(arg.span, &format!("unknown JS format `{}`, expected `pretty` or `minified`", value)); }
+                }
+            },
+            ("source_map", Some(ref value)) => {
+                match SourceMapMode::from_name(value) {
+                    Some(source_map) => { config.source_map = source_map; },
+                    None => { reg.sess.span_err(arg.span, &format!("unknown source map mode `{}`, expected `none`, `inline` or `file`", value)); }
+                }
+            },
+            _ => {
+                reg.sess.span_err(arg.span, "unknown incrust_plugin option, expected one of `store_runtime`, `js_backend`, `js_module`, `runtime_module`, `source_map` or `js_format`");
+            }
+        }
+    }
+
+    config
+}
 
 #[plugin_registrar]
 pub fn plugin_registrar(reg: &mut Registry) {
+    let config = plugin_config(reg);
+
     reg.register_syntax_extension(token::intern("template"),
-            IdentTT(Box::new(template_syntax::expander::expand_template), None, false));
+            IdentTT(Box::new(template_syntax::expander::TemplateExpander::new(config)), None, false));
 
     reg.register_syntax_extension(token::intern("render_output"),
             NormalTT(Box::new(template_syntax::expander::expand_render_output), None, false));
+
+    // #[derive(IncrustModel)], requires #![feature(custom_derive)]
+    reg.register_syntax_extension(token::intern("derive_IncrustModel"),
+            MultiDecorator(Box::new(model_derive::expander::expand_derive_model)));
 }
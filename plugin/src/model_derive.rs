@@ -0,0 +1,200 @@
+
+pub mod expander {
+    use syntax::ast;
+    use syntax::ext::base::{ExtCtxt, Annotatable};
+    use syntax::ext::build::AstBuilder;
+    use syntax::codemap::Span;
+    use syntax::print::pprust::ty_to_string;
+    use syntax::ptr::P;
+
+    use incrust_common::types::ValueType;
+    use incrust_common::nodes::model_node::{Model, ModelField, ModelVariant};
+    use incrust_common::model::{registry, ENUM_TAG, ENUM_VALUES};
+
+    /// JS constructor taking the field values in declaration order, with an accessor for each field,
+    /// and a `fromJSON` helper to hydrate from the serialized model.
+    fn js_factory_source(model_name: &str, field_names: &[String]) -> String {
+        let data_members: Vec<String> = field_names.iter()
+            .map(|name| format!("{}: _args.shift()", name))
+            .collect();
+        let accessors: Vec<String> = field_names.iter()
+            .map(|name| format!("{}: function() {{ return _d.{}; }}", name, name))
+            .collect();
+        let json_args: Vec<String> = field_names.iter()
+            .map(|name| format!("o.{}", name))
+            .collect();
+
+        format!("function {}() {{ var _args = [].slice.call(arguments); var _d = {{ {} }}; return Object.create({{ {} }}); }}; {}.fromJSON = function(o) {{ return {}({}); }};",
+            model_name, data_members.join(", "), accessors.join(", "),
+            model_name, model_name, json_args.join(", "))
+    }
+
+    /// JS object with a constructor for each variant, building the serialized form matched on by views,
+    /// e.g. `Load.Loaded(data)`, and a `fromJSON` helper as with structs.
+    fn js_enum_factory_source(model_name: &str, variant_names: &[String]) -> String {
+        let tags: Vec<String> = variant_names.iter().map(|name| format!("\"{}\"", name)).collect();
+
+        format!("var {0} = {{}}; [{1}].forEach(function(tag) {{ {0}[tag] = function() {{ var o = {{}}; o.{2} = tag; o.{3} = [].slice.call(arguments); return o; }}; }}); {0}.fromJSON = function(o) {{ return o; }};",
+            model_name, tags.join(", "), ENUM_TAG, ENUM_VALUES)
+    }
+
+    /// #[derive(IncrustModel)] on an enum of unit and tuple variants, serialized with the variant name
+    /// and the values of the variant, so views can `match` on it
+    fn expand_derive_enum(ecx: &mut ExtCtxt, span: Span, item: &ast::Item, enum_def: &ast::EnumDef, push: &mut FnMut(Annotatable)) {
+        let name = item.ident;
+        let model_name = name.name.to_string();
+
+        let mut variants = Vec::new();
+        let mut variant_exprs = Vec::new();
+        let mut to_value_arms = Vec::new();
+        for variant in &enum_def.variants {
+            let variant_ident = variant.node.name;
+            let variant_name = variant_ident.name.to_string();
+            let fields = match variant.node.data {
+                ast::VariantData::Unit(_) => vec![],
+                ast::VariantData::Tuple(ref fields, _) => fields.clone(),
+                ast::VariantData::Struct(..) => {
+                    ecx.span_err(variant.span, "#[derive(IncrustModel)] supports unit and tuple variants only");
+                    continue;
+                }
+            };
+
+            let type_names: Vec<String> = fields.iter().map(|field| field_type_name(&field.ty)).collect();
+            let value_types = type_names.iter().map(|type_name| ValueType::from_type_name(type_name)).collect();
+            variants.push(ModelVariant::new(variant.span, &variant_name, value_types));
+
+            let type_exprs: Vec<P<ast::Expr>> = type_names.iter().map(|type_name| quote_expr!(ecx, $type_name)).collect();
+            let types_expr = ecx.expr_vec(span, type_exprs);
+            variant_exprs.push(quote_expr!(ecx, ($variant_name, $types_expr.to_vec())));
+
+            // `&Name::Variant(ref value_0, ...) => Value::variant_of("Variant", [value_0.to_value(), ...].to_vec())`
+            let path = ecx.path(span, vec![name, variant_ident]);
+            let value_idents: Vec<ast::Ident> = (0..fields.len()).map(|idx| ecx.ident_of(&format!("value_{}", idx))).collect();
+            let pat = if fields.is_empty() {
+                ecx.pat_path(span, path)
+            } else {
+                let subpats = value_idents.iter()
+                    .map(|&ident| ecx.pat_ident_binding_mode(span, ident, ast::BindingMode::ByRef(ast::Mutability::Immutable)))
+                    .collect();
+                ecx.pat_enum(span, path, subpats)
+            };
+            let value_exprs: Vec<P<ast::Expr>> = value_idents.iter()
+                .map(|&ident| quote_expr!(ecx, ::incrust_common::model::ToValue::to_value($ident)))
+                .collect();
+            let values_expr = ecx.expr_vec(span, value_exprs);
+            let value_expr = quote_expr!(ecx, ::incrust_common::value::Value::variant_of($variant_name, $values_expr.to_vec()));
+            to_value_arms.push(ecx.arm(span, vec![pat], value_expr));
+        }
+
+        registry::register_model(Model::new_enum(item.span, &model_name, variants.clone()));
+
+        let variant_names: Vec<String> = variants.iter().map(|variant| variant.name().to_owned()).collect();
+        let js_factory = js_enum_factory_source(&model_name, &variant_names);
+        let variants_expr = ecx.expr_vec(span, variant_exprs);
+        let match_expr = ecx.expr_match(span, quote_expr!(ecx, *self), to_value_arms);
+
+        let model_impl = quote_item!(ecx,
+            impl ::incrust_common::model::IncrustModel for $name {
+                fn model_name() -> &'static str { $model_name }
+                fn model_fields() -> Vec<(&'static str, &'static str)> { vec![] }
+                fn model_variants() -> Vec<(&'static str, Vec<&'static str>)> { $variants_expr.to_vec() }
+                fn js_factory() -> &'static str { $js_factory }
+            }
+        ).unwrap();
+
+        let to_value_impl = quote_item!(ecx,
+            impl ::incrust_common::model::ToValue for $name {
+                fn to_value(&self) -> ::incrust_common::value::Value {
+                    $match_expr
+                }
+            }
+        ).unwrap();
+
+        push(Annotatable::Item(model_impl));
+        push(Annotatable::Item(to_value_impl));
+    }
+
+    /// Rust type name as used by the template compiler, e.g. `&'static str` => `str`
+    fn field_type_name(ty: &ast::Ty) -> String {
+        let type_name = ty_to_string(ty);
+        let type_name = type_name.trim_left_matches('&');
+        type_name.split_whitespace().last().unwrap_or("").to_owned()
+    }
+
+    /// Macro implementation: #[derive(IncrustModel)] registers the struct (or enum) as a model for templates
+    /// compiled later in the crate, and implements IncrustModel and ToValue for it.
+    pub fn expand_derive_model(ecx: &mut ExtCtxt, span: Span, _: &ast::MetaItem, item: &Annotatable, push: &mut FnMut(Annotatable)) {
+        let item = match item {
+            &Annotatable::Item(ref item) => item,
+            _ => {
+                ecx.span_err(span, "#[derive(IncrustModel)] may only be applied to structs and enums");
+                return;
+            }
+        };
+
+        let fields = match item.node {
+            ast::ItemKind::Struct(ast::VariantData::Struct(ref fields, _), _) => fields,
+            ast::ItemKind::Enum(ref enum_def, _) => {
+                expand_derive_enum(ecx, span, item, enum_def, push);
+                return;
+            },
+            _ => {
+                ecx.span_err(item.span, "#[derive(IncrustModel)] requires a struct with named fields");
+                return;
+            }
+        };
+
+        let name = item.ident;
+        let model_name = name.name.to_string();
+
+        let mut model_fields = Vec::new();
+        let mut field_idents = Vec::new();
+        for field in fields {
+            if let Some(field_ident) = field.ident {
+                let field_name = field_ident.name.to_string();
+                let type_name = field_type_name(&field.ty);
+                model_fields.push(ModelField::new(field.span, &field_name, ValueType::from_type_name(&type_name)));
+                field_idents.push((field_ident, field_name, type_name));
+            }
+        }
+
+        registry::register_model(Model::new(item.span, &model_name, model_fields));
+
+        let field_names: Vec<String> = field_idents.iter().map(|&(_, ref field_name, _)| field_name.to_owned()).collect();
+        let js_factory = js_factory_source(&model_name, &field_names);
+
+        let field_exprs: Vec<P<ast::Expr>> = field_idents.iter()
+            .map(|&(_, ref field_name, ref type_name)| quote_expr!(ecx, ($field_name, $type_name)))
+            .collect();
+        let fields_expr = ecx.expr_vec(span, field_exprs);
+
+        let to_value_stmts: Vec<ast::Stmt> = field_idents.iter()
+            .map(|&(ref field_ident, ref field_name, _)| {
+                quote_stmt!(ecx,
+                    members.insert($field_name.to_owned(), ::incrust_common::model::ToValue::to_value(&self.$field_ident));
+                ).unwrap()
+            })
+            .collect();
+
+        let model_impl = quote_item!(ecx,
+            impl ::incrust_common::model::IncrustModel for $name {
+                fn model_name() -> &'static str { $model_name }
+                fn model_fields() -> Vec<(&'static str, &'static str)> { $fields_expr.to_vec() }
+                fn js_factory() -> &'static str { $js_factory }
+            }
+        ).unwrap();
+
+        let to_value_impl = quote_item!(ecx,
+            impl ::incrust_common::model::ToValue for $name {
+                fn to_value(&self) -> ::incrust_common::value::Value {
+                    let mut members = ::std::collections::BTreeMap::new();
+                    $to_value_stmts
+                    ::incrust_common::value::Value::Object(members)
+                }
+            }
+        ).unwrap();
+
+        push(Annotatable::Item(model_impl));
+        push(Annotatable::Item(to_value_impl));
+    }
+}
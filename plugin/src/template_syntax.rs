@@ -2,7 +2,7 @@
 
 pub mod expander {
     use syntax::ast;
-    use syntax::ext::base::{ExtCtxt, MacResult, DummyResult, MacEager};
+    use syntax::ext::base::{ExtCtxt, MacResult, DummyResult, MacEager, IdentMacroExpander};
     use syntax::ext::build::AstBuilder;
     use syntax::parse::parser::Parser;
     use syntax::parse::{token, PResult};
@@ -11,49 +11,86 @@ pub mod expander {
     use syntax::codemap::{DUMMY_SP, Span};
     use syntax::ptr::P;
 
-    use incrust_common::codegen::lang::{Html, Js};
+    use incrust_common::codegen::lang::{Html, Js, Ts};
     use incrust_common::nodes::template_node::parse::parse_template;
+    use incrust_common::typecheck::check_template;
+    use incrust_common::diagnostics::has_errors;
+    use incrust_common::config::Config;
+    use incrust_common::js_write::output_strings::JsOutput;
+    use incrust_common::runtime::Runtime;
+    use incrust_common::bundle::Bundle;
 
     use incrust_common::codegen::output_item_writer::IntoOutputItem;
-    use incrust_common::codegen::named_output::NamedOutput;
+    use incrust_common::codegen::naming::render_fn_name;
 
     pub enum RenderLang {
         RenderHtml,
-        RenderJs
+        RenderJs,
+        RenderTs
     }
 
-    fn process_contents<'cx, 'r>(ecx: &'cx mut ExtCtxt<'r>, span: Span, ident: ast::Ident, mut parser: &mut Parser) -> Box<MacResult + 'cx> {
+    fn process_contents<'cx, 'r>(ecx: &'cx mut ExtCtxt<'r>, span: Span, ident: ast::Ident, mut parser: &mut Parser, config: &Config) -> Box<MacResult + 'cx> {
         let template_name = ident.name.to_string();
-        ecx.span_warn(span, &format!("Parsing contents of template {}", &template_name));
+        debug!("Parsing contents of template {}", &template_name);
 
         macro_rules! define_lang_outputs (
             ($ecx: expr, $template: ident, $template_name: expr, $lang: ident) => ({
-                let sources: Vec<P<ast::Item>> = $template.nodes().iter().map(|node| {
-                    $ecx.span_warn(DUMMY_SP, &format!("Source: {:?}", &node));
+                let sources: Vec<P<ast::Item>> = $template.nodes().iter().filter(|node| node.has_output()).map(|node| {
+                    debug!("Source: {:?}", &node);
                     let lang_node: &IntoOutputItem<$lang> = node;
-                    let item = lang_node.into_output_item($ecx, (node as &NamedOutput<$lang>).output_name());
-                    $ecx.span_warn(DUMMY_SP, &format!("Source item: {:?}", &item));
+                    let item = lang_node.into_output_item($ecx, $template_name);
+                    debug!("Source item: {:?}", &item);
                     item
                 }).collect();
                 sources
             })
         );
 
-        match parse_template(ecx, &mut parser, span, &template_name) {
-            Ok(template) => {
-                ecx.span_warn(span, &format!("Parsed template: {:?}", &template));
+        let (template, diagnostics) = parse_template(ecx, &mut parser, span, &template_name);
+        debug!("Parsed template: {:?}", &template);
 
-                let mut items = Vec::new();
-                items.append(&mut define_lang_outputs!(ecx, template, "main", Html));
-                items.append(&mut define_lang_outputs!(ecx, template, "main", Js));
-                MacEager::items(SmallVector::many(items))
-            },
+        let failed = has_errors(&diagnostics);
+        for diagnostic in diagnostics {
+            diagnostic.emit(&ecx.parse_sess.span_diagnostic);
+        }
 
-            Err(mut err) => {
-                err.emit();
-                DummyResult::expr(span)
-            }
+        // Type errors in a partially parsed template would mostly be noise
+        if failed {
+            return DummyResult::any(span);
+        }
+
+        // Errors are reported at the expression spans, but we still emit items
+        // to avoid a cascade of unresolved render function errors.
+        check_template(ecx, &template);
+
+        let mut items = Vec::new();
+        items.append(&mut define_lang_outputs!(ecx, template, template.name(), Html));
+        for node in template.nodes().iter().filter(|node| node.has_output()) {
+            let js_item = (&JsOutput::new(node, config) as &IntoOutputItem<Js>).into_output_item(ecx, template.name());
+            items.push(js_item);
         }
+
+        // The root store combines all stores in the template
+        let root_store = template.root_store(config.store_runtime);
+        let root_store_item = (&JsOutput::new(&root_store, config) as &IntoOutputItem<Js>).into_output_item(ecx, template.name());
+        items.push(root_store_item);
+
+        // Runtime support for the configured store and rendering backend
+        let runtime = Runtime::new(template.name(), config);
+        let runtime_item = (&JsOutput::new(&runtime, config) as &IntoOutputItem<Js>).into_output_item(ecx, template.name());
+        items.push(runtime_item);
+
+        // All of the above in one output, with its source map
+        let bundle = Bundle::new(&template, config);
+        let bundle_item = (&bundle as &IntoOutputItem<Js>).into_output_item(ecx, template.name());
+        items.push(bundle_item);
+        let source_map_item = (&bundle.source_map() as &IntoOutputItem<Js>).into_output_item(ecx, template.name());
+        items.push(source_map_item);
+
+        // TypeScript declarations cover the whole template
+        let ts_item = (&template as &IntoOutputItem<Ts>).into_output_item(ecx, template.name());
+        items.push(ts_item);
+        MacEager::items(SmallVector::many(items))
     }
 
     fn process_render<'cx, 'r>(ecx: &'cx ExtCtxt, span: Span, mut parser: &mut Parser<'r>) -> PResult<'r, Box<MacResult + 'cx>> {
@@ -69,9 +106,21 @@ pub mod expander {
         try!(parser.expect(&token::Comma));
         let lang = try!(parser.parse_ident());
 
+        // Optional root state, with the state of each store under its name
+        let state = if parser.eat(&token::Comma) {
+            try!(parser.parse_expr())
+        } else {
+            quote_expr!(ecx, &::incrust_common::value::Value::Null)
+        };
+
         match output_ty {
             _ if output_ty == "view" => (),
             _ if output_ty == "store" => (),
+            _ if output_ty == "root_store" => (),
+            _ if output_ty == "runtime" => (),
+            _ if output_ty == "bundle" => (),
+            _ if output_ty == "source_map" => (),
+            _ if output_ty == "types" => (),
             _ => {
                 ecx.span_fatal(span, &format!("Unsupported output type."));
             }
@@ -81,6 +130,7 @@ pub mod expander {
         match &lang_str {
             _ if lang_str == "html" => RenderLang::RenderHtml,
             _ if lang_str == "js" => RenderLang::RenderJs,
+            _ if lang_str == "ts" => RenderLang::RenderTs,
             _ => {
                 ecx.span_fatal(span, &format!("Unsupported render language."));
             }
@@ -88,24 +138,38 @@ pub mod expander {
 
         // example: rusttemplate_render_template_main_view_root_html
         // example: rusttemplate_render_template_main_store_counter_js
-        let render_ident = ecx.ident_of(&format!("rusttemplate_render_template_{}_{}_{}_{}", &template_name, &output_ty, &output_name, &lang_str));
-        let expr = quote_expr!(ecx, {
-            $render_ident($html_writer, $js_writer);
-        });
-        let stmt = ecx.stmt_expr(expr);
+        // example: rusttemplate_render_template_main_root_store_main_js
+        // example: rusttemplate_render_template_main_bundle_main_js
+        // example: rusttemplate_render_template_main_types_main_ts
+        let render_ident = ecx.ident_of(&render_fn_name(&template_name, &output_ty, &output_name, &lang_str));
+        // Evaluates to the `RenderResult` of the render function
+        let expr = quote_expr!(ecx, $render_ident($html_writer, $js_writer, $state));
+
+        Ok(MacEager::expr(expr))
+    }
 
-        Ok(MacEager::stmts(SmallVector::one(stmt)))
+    /// Expander for `template!`, generating code with the options given to the plugin
+    pub struct TemplateExpander {
+        config: Config
     }
 
-    /// Macro implementation: create a set of macros of the form emit_$lang_view_$template!($output_var);
-    /// which will render the parsed template in the given language.
-    pub fn expand_template<'cx, 'r>(ecx: &'cx mut ExtCtxt<'r>, span: Span, ident: ast::Ident, tts: Vec<TokenTree>) -> Box<MacResult + 'cx> {
-        let mut parser = ecx.new_parser_from_tts(&tts);
-        process_contents(ecx, span, ident, &mut parser)
+    impl TemplateExpander {
+        pub fn new(config: Config) -> TemplateExpander {
+            TemplateExpander { config: config }
+        }
+    }
+
+    impl IdentMacroExpander for TemplateExpander {
+        /// Macro implementation: create a set of macros of the form emit_$lang_view_$template!($output_var);
+        /// which will render the parsed template in the given language.
+        fn expand<'cx>(&self, ecx: &'cx mut ExtCtxt, span: Span, ident: ast::Ident, tts: Vec<TokenTree>) -> Box<MacResult + 'cx> {
+            let mut parser = ecx.new_parser_from_tts(&tts);
+            process_contents(ecx, span, ident, &mut parser, &self.config)
+        }
     }
 
-    /// Macro implementation: render named output in template, with output name
-    /// ($html_writer: ident, $js_writer: ident, $output_ty: ident, $template_name: ident, $output_name: ident, $render_lang: ident)
+    /// Macro implementation: render named output in template, with output name, returning the `RenderResult`
+    /// ($html_writer: ident, $js_writer: ident, $output_ty: ident, $template_name: ident, $output_name: ident, $render_lang: ident[, $state: expr])
     pub fn expand_render_output<'cx, 'r>(ecx: &'cx mut ExtCtxt<'r>, span: Span, tts: &[TokenTree]) -> Box<MacResult + 'cx> {
         let mut parser = ecx.new_parser_from_tts(&tts);
         match process_render(ecx, span, &mut parser) {
@@ -0,0 +1,45 @@
+#![feature(plugin, custom_derive)]
+#![plugin(incrust_plugin)]
+
+extern crate incrust_common;
+
+use incrust_common::model::{IncrustModel, ToValue};
+use incrust_common::value::Value;
+
+
+#[derive(IncrustModel)]
+struct Person {
+    first_name: String,
+    age: i64,
+    nickname: &'static str
+}
+
+#[derive(IncrustModel)]
+enum Load {
+    Loading,
+    Loaded(Person),
+    Failed(String)
+}
+
+#[test]
+fn test_derive_model_fields() {
+    assert_eq!(Person::model_name(), "Person");
+    assert_eq!(Person::model_fields(), vec![("first_name", "String"), ("age", "i64"), ("nickname", "str")]);
+
+    assert_eq!(Load::model_name(), "Load");
+    assert_eq!(Load::model_variants(), vec![("Loading", vec![]), ("Loaded", vec!["Person"]), ("Failed", vec!["String"])]);
+}
+
+#[test]
+fn test_derive_to_value() {
+    let person = Person { first_name: "Ada".to_owned(), age: 36, nickname: "ada" };
+    let value = person.to_value();
+    assert_eq!(value.lookup("first_name"), Some(&Value::from("Ada")));
+    assert_eq!(value.lookup("age"), Some(&Value::Number(36.0)));
+    assert_eq!(value.lookup("nickname"), Some(&Value::from("ada")));
+
+    let load = Load::Failed("timeout".to_owned()).to_value();
+    assert_eq!(load.variant(), Some("Failed"));
+    assert_eq!(load.lookup("values.0"), Some(&Value::from("timeout")));
+    assert_eq!(Load::Loading.to_value(), Value::variant_of("Loading", vec![]));
+}
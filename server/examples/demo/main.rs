@@ -1,8 +1,9 @@
 #![feature(plugin)]
-#![plugin(incrust_plugin)]
+#![plugin(incrust_plugin(store_runtime = "builtin"))]
 
 #[macro_use]
 extern crate nickel;
+extern crate incrust_common;
 #[macro_use]
 extern crate incrust_macros;
 #[macro_use]
@@ -17,13 +18,13 @@ template! main {
     }
 
     view root [
-        div [
-            h1 [ {"Counter: "}{counter} ]
+        div(class="counter", class:started=counter) [
+            h1(style={ font-size: "2em" }) [ "Counter: {counter}" ]
         ]
     ]
 }
 
-example!(main, root, counter, r"
+example!(main, root, r"
     function start(store) {
         setInterval(function() { store.dispatch({type: 'INCREMENT'}); }, 1000);
     };");
@@ -1,34 +1,43 @@
-#![feature(plugin)]
+#![feature(plugin, custom_derive)]
 #![plugin(incrust_plugin)]
 
 #[macro_use]
 extern crate nickel;
+extern crate incrust_common;
 #[macro_use]
 extern crate incrust_macros;
 #[macro_use]
 extern crate examples_common;
 
 mod models;
-use models::person_js;
+use std::collections::BTreeMap;
+use models::Person;
+use incrust_common::model::{IncrustModel, ToValue};
+use incrust_common::value::Value;
 
 
 template! main {
-    store person {
-        default => ("{}");
-        action SET_FIRST_NAME => ("{first_name: \"first_name\"}");
-        action SET_LAST_NAME => ("{last_name: \"last_name\"}")
-    }
+    // SET_PERSON_FIRST_NAME and SET_PERSON_LAST_NAME are generated for the bound inputs.
+    // The initial person is rendered from the state passed in below.
+    store person: Person { }
 
     view root [
-        p [ {"First name:  "} {(data.first_name)} ]
-        p [ {"Last name:  "} {(data.last_name)} ]
+        p [ "First name:  {person.first_name}" ]
+        p [ "Last name:  {person.last_name}" ]
         div [
-            form [
-                input []
-                input []
+            form(class="person") [
+                input(type="text", name="first_name", bind:value=person.first_name) []
+                input(type="text", name="last_name", bind:value=person.last_name) []
             ]
         ]
     ]
 }
 
-example!(main, root, person, person_js(), "function(store) { setInterval(function() { store.dispatch({type: 'SET_FIRST_NAME'}); }, 1000); });");
\ No newline at end of file
+/// Root state of the template, with the state of each store under its name
+fn initial_state() -> Value {
+    let mut state = BTreeMap::new();
+    state.insert("person".to_owned(), Person::new("Ada", "Lovelace").to_value());
+    Value::from(state)
+}
+
+example!(main, root, state = initial_state(), Person::js_factory(), "function start(store) {}");
\ No newline at end of file
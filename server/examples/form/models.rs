@@ -1,24 +1,14 @@
-use std::fmt::Write;
-
-#[macro_use]
-use examples_common::models;
-
-
+#[derive(IncrustModel)]
 pub struct Person {
     first_name: String,
     last_name: String
 }
 
 impl Person {
+    pub fn new(first_name: &str, last_name: &str) -> Person {
+        Person { first_name: first_name.to_owned(), last_name: last_name.to_owned() }
+    }
+
     pub fn first_name(&self) -> &str { &self.first_name }
     pub fn last_name(&self) -> &str { &self.last_name }
 }
-
-pub fn person_js() -> String {
-    data_struct! {
-        struct Person {
-            first_name: String,
-            last_name: String
-        }
-    }
-}
@@ -7,5 +7,4 @@ extern crate incrust_common;
 #[macro_use]
 extern crate incrust_macros;
 
-pub mod models;
 pub mod render;
\ No newline at end of file
@@ -76,7 +76,9 @@ macro_rules! render_example_page {
 
 #[macro_export]
 macro_rules! example {
-    ($template_name: ident, $view_name: ident, $store_name: ident, $($extra_js: expr),*) => (
+    // The page is rendered from `state`, a `Value` holding the state of each store under its name,
+    // which also becomes the initial state of the client side store
+    ($template_name: ident, $view_name: ident, state = $state: expr, $($extra_js: expr),*) => (
         use std::path::Path;
         use std::fmt::Write;
         use nickel::{ Nickel, HttpRouter, StaticFilesHandler };
@@ -90,6 +92,11 @@ macro_rules! example {
 
         fn render() -> String {
             let mut page = String::new();
+            let state: ::incrust_common::value::Value = $state;
+            let initial_state = match state {
+                ::incrust_common::value::Value::Null => String::new(),
+                ref state => state.to_json()
+            };
             macro_rules! script_src (($uri:expr) => (concat!["<script src=\"", $uri, "\"></script>"]));
 
             let mut main_html = String::new();
@@ -101,13 +108,13 @@ macro_rules! example {
                     document.addEventListener('DOMContentLoaded', function() {
                         var view = view_factory();
                         var root = document.querySelector('#root');
+                        var store = store_factory();
+
+                        // Bound inputs dispatch to the store
                         function render(state) {
-                            console.log('Patching IncrementalDOM');
-                            IncrementalDOM.patch(root, view, state);
+                            patch_view(root, view, state, store.dispatch);
                         }
 
-                        var store = store_factory();
-
                         // Subscribe to updates
                         store.subscribe(function() {
                             render(store.getState());
@@ -129,18 +136,19 @@ macro_rules! example {
                     {}
                     {}
                     {}
+                    {}
                 }})();
                 </script>",
                 &extra_js,
-                format!("function store_factory() {{ return Redux.createStore(rusttemplate_store_template_{}_{}); }};", stringify!($template_name), stringify!($store_name)),
+                format!("function store_factory() {{ return rusttemplate_create_store_template_{}({}); }};", stringify!($template_name), &initial_state),
                 format!("function view_factory() {{ return rusttemplate_render_template_{}_view_{}_calls; }};", stringify!($template_name), stringify!($view_name)),
+                format!("function patch_view(root, view, state, dispatch) {{ return rusttemplate_patch_template_{}(root, view, state, dispatch); }};", stringify!($template_name)),
                 entry
             ).unwrap();
 
             // Render Rust and JS main template
-            render_output!(&mut main_html, &mut main_js, $template_name, view, $view_name, Html);
-            render_output!(&mut main_html, &mut main_js, $template_name, view, $view_name, Js);
-            render_output!(&mut main_html, &mut main_js, $template_name, store, $store_name, Js);
+            render_output!(&mut main_html, &mut main_js, $template_name, view, $view_name, Html, &state).unwrap();
+            render_output!(&mut main_html, &mut main_js, $template_name, bundle, $template_name, Js).unwrap();
             println!("Rendered main template: [{}]", &main_html);
 
             // Output HTML template
@@ -160,13 +168,26 @@ macro_rules! example {
             page
         }
 
+        // TypeScript declarations for the generated JS
+        fn render_types() -> String {
+            let mut unused_html = String::new();
+            let mut types = String::new();
+            render_output!(&mut unused_html, &mut types, $template_name, types, $template_name, Ts).unwrap();
+            types
+        }
+
         fn main() {
             let mut server = Nickel::new();
             server.utilize(statics());
+            server.get("/app.d.ts", middleware!(render_types()));
             server.get("**", middleware!(render()));
 
             server.listen("127.0.0.1:6767").unwrap();
         }
+    );
+
+    ($template_name: ident, $view_name: ident, $($extra_js: expr),*) => (
+        example!($template_name, $view_name, state = ::incrust_common::value::Value::Null, $($extra_js),*);
     )
 }
 